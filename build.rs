@@ -0,0 +1,397 @@
+// Converts the bitmaps in `assets/` into pixel arrays at compile time, so the
+// game never has to parse a BMP header on the board. The pixels are run-length
+// encoded (see `src/rle.rs`) to save flash. The result is written to
+// `$OUT_DIR/assets.rs` and included by `src/assets.rs`.
+//
+// A table comparing the size of each bitmap file, its decoded pixels and the
+// compressed data (savings are relative to the bitmap) is written to
+// `$OUT_DIR/asset_report.txt`. Set `ASSET_REPORT=1` to also print it during
+// the build.
+//
+// Every song in `SONGS` is checked (see `src/music.rs`), a mistake fails the
+// build. `host/songs.rs` plays them and writes them to WAV files.
+
+extern crate core;
+
+#[allow(dead_code)]
+#[path = "src/mixer.rs"]
+mod mixer;
+#[allow(dead_code)]
+#[path = "src/music.rs"]
+mod music;
+#[path = "src/rle.rs"]
+mod rle;
+
+use rle::Pixel;
+use std::env;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Framebuffer format a sprite is converted to.
+#[derive(Clone, Copy)]
+enum Format {
+    /// layer 1, one `u32` per pixel
+    Argb8888,
+    /// layer 2, one `u16` per pixel, see `to_al88`
+    Al88 { dither: bool },
+}
+
+/// (constant name, path relative to the crate root, target format)
+const ASSETS: &[(&str, &str, Format)] = &[
+    ("PAUSE_SCREEN_RIGHT", "assets/Pause_screen_snake_left.bmp", Format::Al88 { dither: false }),
+    ("PAUSE_SCREEN_LEFT", "assets/Pause_screen_snake_right.bmp", Format::Al88 { dither: false }),
+    ("PAUSE_SCREEN_GAME_OVER", "assets/Pause_screen_game_over.bmp", Format::Al88 { dither: false }),
+    ("PAUSE_SCREEN_PAUSE", "assets/Pause_screen_pause.bmp", Format::Al88 { dither: false }),
+    ("PAUSE_SCREEN_RESUME", "assets/Pause_screen_resume.bmp", Format::Al88 { dither: false }),
+    ("PAUSE_SCREEN_NEW_GAME", "assets/Pause_screen_New_game.bmp", Format::Al88 { dither: false }),
+    ("WELCOME_SCREEN_BASE", "assets/Welcom_screen/Snake_base2.bmp", Format::Argb8888),
+    ("WELCOME_SCREEN_OPEN_MOUTH", "assets/Welcom_screen/Snake_mouth_open.bmp", Format::Argb8888),
+    ("WELCOME_SCREEN_CLOSED_MOUTH", "assets/Welcom_screen/Snake_mouth_shut.bmp", Format::Argb8888),
+    ("APPLE", "assets/apple.bmp", Format::Argb8888),
+    ("APPLE_SMALL", "assets/apple_small.bmp", Format::Argb8888),
+    ("SNAKE_MOUTH_CLOSED", "assets/snake_head_closed.bmp", Format::Argb8888),
+    ("SNAKE_MOUTH_OPEN", "assets/snake_head_opened.bmp", Format::Argb8888),
+    ("SNAKE_BODY_STRAIGHT", "assets/snake_body_straight.bmp", Format::Argb8888),
+    ("SNAKE_BODY_CORNER", "assets/snake_body_corner.bmp", Format::Argb8888),
+    ("SNAKE_TAIL", "assets/snake_tail.bmp", Format::Argb8888),
+];
+
+/// (name, song)
+const SONGS: &[(&str, &str)] = &[("theme", music::THEME)];
+
+/// Pixels brighter than this in all three channels are treated as transparent.
+const TRANSPARENT_THRESHOLD: u8 = 245;
+
+/// 4x4 Bayer matrix for ordered dithering.
+const BAYER: [[u32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// A decoded bitmap, rows stored top to bottom.
+struct Bitmap {
+    width: usize,
+    height: usize,
+    /// (red, green, blue, alpha)
+    pixels: Vec<(u8, u8, u8, u8)>,
+    /// the file has an alpha channel, otherwise all pixels are opaque and
+    /// white ones are meant to be transparent
+    has_alpha: bool,
+}
+
+impl Bitmap {
+    fn pixel(&self, x: usize, y: usize) -> (u8, u8, u8, u8) {
+        self.pixels[y * self.width + x]
+    }
+}
+
+fn read_u16(data: &[u8], at: usize) -> u16 {
+    u16::from(data[at]) | u16::from(data[at + 1]) << 8
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from(read_u16(data, at)) | u32::from(read_u16(data, at + 2)) << 16
+}
+
+/// Decodes an uncompressed 24 or 32 bit BMP. Bottom-up and top-down files are
+/// both returned with the top row first. 32 bit files keep their alpha channel.
+fn decode_bmp(path: &str, data: &[u8]) -> Bitmap {
+    assert!(&data[0..2] == b"BM", "{}: not a bmp file", path);
+    let pixels_start = read_u32(data, 10) as usize;
+    let header_size = read_u32(data, 14);
+    let width = read_u32(data, 18) as i32;
+    let height = read_u32(data, 22) as i32;
+    let bits_per_pixel = read_u16(data, 28);
+    let compression = read_u32(data, 30);
+    assert!(
+        bits_per_pixel == 24 || bits_per_pixel == 32,
+        "{}: only 24 and 32 bit bitmaps are supported",
+        path
+    );
+    // 32 bit files may describe their channels with bit masks
+    let masks = if compression == 3 && header_size >= 56 {
+        [read_u32(data, 54), read_u32(data, 58), read_u32(data, 62), read_u32(data, 66)]
+    } else {
+        assert!(compression == 0, "{}: compressed bitmaps are not supported", path);
+        [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0xff00_0000]
+    };
+    assert!(width > 0, "{}: invalid width {}", path, width);
+
+    let width = width as usize;
+    let top_down = height < 0;
+    let height = if top_down { -height } else { height } as usize;
+    let bytes = usize::from(bits_per_pixel / 8);
+    // every row is padded to a multiple of 4 bytes
+    let stride = (width * bytes + 3) & !3;
+    assert!(
+        data.len() >= pixels_start + stride * height,
+        "{}: pixel data is truncated",
+        path
+    );
+
+    let channel = |value: u32, mask: u32| {
+        if mask == 0 {
+            0
+        } else {
+            ((value & mask) >> mask.trailing_zeros()) as u8
+        }
+    };
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = if top_down { y } else { height - 1 - y };
+        let row_start = pixels_start + row * stride;
+        for x in 0..width {
+            let at = row_start + x * bytes;
+            pixels.push(if bytes == 3 {
+                (data[at + 2], data[at + 1], data[at], 255)
+            } else {
+                let value = read_u32(data, at);
+                (
+                    channel(value, masks[0]),
+                    channel(value, masks[1]),
+                    channel(value, masks[2]),
+                    channel(value, masks[3]),
+                )
+            });
+        }
+    }
+    // many programs write 32 bit files with an unused, zeroed alpha channel
+    let has_alpha = bytes == 4 && masks[3] != 0 && pixels.iter().any(|p| p.3 != 0);
+    if bytes == 4 && !has_alpha {
+        for pixel in &mut pixels {
+            pixel.3 = 255;
+        }
+    }
+    Bitmap {
+        width,
+        height,
+        pixels,
+        has_alpha,
+    }
+}
+
+fn is_transparent(bitmap: &Bitmap, &(red, green, blue, alpha): &(u8, u8, u8, u8)) -> bool {
+    if bitmap.has_alpha {
+        return alpha < 128;
+    }
+    red > TRANSPARENT_THRESHOLD && green > TRANSPARENT_THRESHOLD && blue > TRANSPARENT_THRESHOLD
+}
+
+/// Sprites for layer 1 are either opaque or transparent, partial alpha is
+/// rounded.
+fn to_argb8888(bitmap: &Bitmap, pixel: &(u8, u8, u8, u8)) -> u32 {
+    if is_transparent(bitmap, pixel) {
+        return <u32 as Pixel>::TRANSPARENT;
+    }
+    let &(red, green, blue, _) = pixel;
+    0xff00_0000 | u32::from(red) << 16 | u32::from(green) << 8 | u32::from(blue)
+}
+
+/// Luminance (ITU-R BT.601) in 1/256 steps, 0 to 255 * 256. Same weights as
+/// `Color::luminance`.
+fn luminance(red: u8, green: u8, blue: u8) -> u32 {
+    77 * u32::from(red) + 150 * u32::from(green) + 29 * u32::from(blue)
+}
+
+/// Rounds a luminance from `luminance` to 8 bits, with `dither` the rounding
+/// threshold follows a Bayer matrix so smooth gradients do not band.
+fn quantize(luminance: u32, x: usize, y: usize, dither: bool) -> u32 {
+    let threshold = if dither { BAYER[y % 4][x % 4] * 16 + 8 } else { 128 };
+    ((luminance + threshold) >> 8).min(255)
+}
+
+/// Converts a bitmap to AL88 (alpha << 8 | luminance).
+///
+/// Files with an alpha channel keep it. Bitmaps without one are drawn on a
+/// white background: white is transparent, everything else is opaque, except
+/// for the anti-aliased edges next to transparent pixels. Those were blended
+/// with the white background, so their alpha is recovered from how close they
+/// are to the darkest neighbouring color (the ink they were blended from).
+fn to_al88(bitmap: &Bitmap, dither: bool) -> Vec<u32> {
+    let (width, height) = (bitmap.width, bitmap.height);
+    let neighbours = |x: usize, y: usize| {
+        let mut cells = Vec::with_capacity(8);
+        for ny in y.saturating_sub(1)..(y + 2).min(height) {
+            for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                if (nx, ny) != (x, y) {
+                    cells.push(bitmap.pixel(nx, ny));
+                }
+            }
+        }
+        cells
+    };
+
+    let mut out = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = bitmap.pixel(x, y);
+            let (red, green, blue, alpha) = pixel;
+            let mut alpha = u32::from(alpha);
+            let mut luminance = luminance(red, green, blue);
+            if !bitmap.has_alpha {
+                let neighbours = neighbours(x, y);
+                if is_transparent(bitmap, &pixel) {
+                    alpha = 0;
+                } else if neighbours.iter().any(|p| is_transparent(bitmap, p)) {
+                    let ink = neighbours
+                        .iter()
+                        .filter(|p| !is_transparent(bitmap, p))
+                        .map(|&(r, g, b, _)| self::luminance(r, g, b))
+                        .fold(luminance, |a, b| a.min(b));
+                    let white = 255 * 256;
+                    if ink < luminance {
+                        alpha = (white - luminance) * 255 / (white - ink);
+                        luminance = ink;
+                    }
+                }
+            }
+            out.push(if alpha == 0 {
+                u32::from(<u16 as Pixel>::TRANSPARENT)
+            } else {
+                alpha << 8 | quantize(luminance, x, y, dither)
+            });
+        }
+    }
+    out
+}
+
+/// Checks that `data` decodes back to exactly `pixels`.
+fn verify<P: Pixel + Into<u32>>(name: &str, data: &[u8], pixels: &[u32]) {
+    let decoded: Vec<u32> = rle::Decoder::<P>::new(data).map(Into::into).collect();
+    assert!(decoded == pixels, "{}: compressed pixels do not round-trip", name);
+}
+
+/// Appends the sprite definition to `out`, returns the compressed size.
+fn generate(name: &str, path: &str, format: Format, bitmap: &Bitmap, out: &mut String) -> usize {
+    let (bytes, transparent, sprite_type) = match format {
+        Format::Argb8888 => (<u32 as Pixel>::BYTES, <u32 as Pixel>::TRANSPARENT, "Sprite<u32>"),
+        Format::Al88 { .. } => (
+            <u16 as Pixel>::BYTES,
+            u32::from(<u16 as Pixel>::TRANSPARENT),
+            "Sprite<u16>",
+        ),
+    };
+    let pixels: Vec<u32> = match format {
+        Format::Argb8888 => bitmap
+            .pixels
+            .iter()
+            .map(|pixel| to_argb8888(bitmap, pixel))
+            .collect(),
+        Format::Al88 { dither } => to_al88(bitmap, dither),
+    };
+    let data = rle::encode(&pixels, bytes, transparent);
+    match format {
+        Format::Argb8888 => verify::<u32>(name, &data, &pixels),
+        Format::Al88 { .. } => verify::<u16>(name, &data, &pixels),
+    }
+
+    writeln!(out, "/// Generated from `{}`.", path).unwrap();
+    writeln!(out, "pub const {}_WIDTH: usize = {};", name, bitmap.width).unwrap();
+    writeln!(out, "pub const {}_HEIGHT: usize = {};", name, bitmap.height).unwrap();
+    writeln!(out, "static {}_DATA: [u8; {}] = [", name, data.len()).unwrap();
+    for line in data.chunks(16) {
+        out.push_str("   ");
+        for byte in line {
+            write!(out, " 0x{:02x},", byte).unwrap();
+        }
+        out.push('\n');
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(
+        out,
+        "pub static {0}: {1} = Sprite {{ width: {0}_WIDTH, height: {0}_HEIGHT, data: &{0}_DATA, pixel: PhantomData }};\n",
+        name,
+        sprite_type
+    ).unwrap();
+    data.len()
+}
+
+/// Fails the build on a mistake in a song.
+fn check_song(name: &str, song: &str) {
+    if let Err(error) = music::check(song) {
+        panic!("song {}, line {}: {}", name, error.line, error.message);
+    }
+}
+
+/// Cargo takes relative paths as relative to the package that is built, which
+/// is not the crate root for `host/build.rs`.
+fn rerun_if_changed(path: &str) {
+    let path = env::current_dir().unwrap().join(path);
+    println!("cargo:rerun-if-changed={}", path.display());
+}
+
+fn main() {
+    // paths are relative to the crate root, `host/build.rs` includes this file
+    let root = Path::new(file!()).parent().unwrap();
+    if root != Path::new("") {
+        env::set_current_dir(root).unwrap();
+    }
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let mut out = String::new();
+    let mut report = String::new();
+    let (mut total_bmp, mut total_raw, mut total_compressed) = (0, 0, 0);
+    writeln!(
+        report,
+        "{:<28} {:>10} {:>10} {:>11} {:>7}",
+        "asset", "bmp", "raw", "compressed", "saved"
+    ).unwrap();
+
+    rerun_if_changed("build.rs");
+    // the modules included above
+    for path in &["src/mixer.rs", "src/music.rs", "src/rle.rs"] {
+        rerun_if_changed(path);
+    }
+    for &(name, path, format) in ASSETS {
+        rerun_if_changed(path);
+        let mut data = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .unwrap_or_else(|e| panic!("{}: {}", path, e));
+        let bitmap = decode_bmp(path, &data);
+        let compressed = generate(name, path, format, &bitmap, &mut out);
+
+        let raw = bitmap.pixels.len() * match format {
+            Format::Argb8888 => 4,
+            Format::Al88 { .. } => 2,
+        };
+        writeln!(
+            report,
+            "{:<28} {:>10} {:>10} {:>11} {:>6.1}%",
+            name,
+            data.len(),
+            raw,
+            compressed,
+            100.0 - 100.0 * compressed as f64 / data.len() as f64
+        ).unwrap();
+        total_bmp += data.len();
+        total_raw += raw;
+        total_compressed += compressed;
+    }
+    writeln!(
+        report,
+        "{:<28} {:>10} {:>10} {:>11} {:>6.1}%",
+        "total",
+        total_bmp,
+        total_raw,
+        total_compressed,
+        100.0 - 100.0 * total_compressed as f64 / total_bmp as f64
+    ).unwrap();
+
+    println!("cargo:rerun-if-env-changed=ASSET_REPORT");
+    if env::var_os("ASSET_REPORT").is_some() {
+        for line in report.lines() {
+            println!("cargo:warning={}", line);
+        }
+    }
+    File::create(Path::new(&out_dir).join("asset_report.txt"))
+        .and_then(|mut file| file.write_all(report.as_bytes()))
+        .unwrap();
+
+    File::create(Path::new(&out_dir).join("assets.rs"))
+        .and_then(|mut file| file.write_all(out.as_bytes()))
+        .unwrap();
+
+    rerun_if_changed("assets/music");
+    for &(name, song) in SONGS {
+        check_song(name, song);
+    }
+}
@@ -0,0 +1,5 @@
+// Generates the assets like the build of the game does, with its build script.
+
+#![allow(unknown_lints, ellipsis_inclusive_range_patterns, mismatched_lifetime_syntaxes)]
+
+include!("../build.rs");
@@ -0,0 +1,182 @@
+//! Renders screens of the game into a `RgbaBuffer` and compares them with the
+//! reference images in `golden/`. Like a screenshot, the images show both
+//! layers on the opaque background of the LTDC.
+//!
+//! After a change to the look, run the tests with `UPDATE_GOLDEN=1` to write
+//! the references anew and check the new images before committing them. A
+//! render that differs from its reference is written next to it as
+//! `<name>.actual.png`.
+
+use assets;
+use canvas::{Canvas, Color, RgbaBuffer};
+use png;
+use render::{self, BoardSize, CellContent, LAYOUT_SIZE};
+use rules::{Goal, Rules};
+use screenshot;
+use sprite::RotDirection;
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use theme;
+
+fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("golden")
+        .join(name)
+}
+
+fn rgba_bytes(buffer: &RgbaBuffer) -> Vec<u8> {
+    buffer
+        .pixels()
+        .iter()
+        .flat_map(|pixel| vec![pixel.red, pixel.green, pixel.blue, pixel.alpha])
+        .collect()
+}
+
+fn write_png(path: &PathBuf, buffer: &RgbaBuffer) {
+    let file = File::create(path).unwrap();
+    let width = buffer.width() as u32;
+    let height = buffer.height() as u32;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&rgba_bytes(buffer)).unwrap();
+}
+
+/**
+ * The size and RGBA bytes of a reference image, `None` if there is none.
+ */
+fn read_png(path: &PathBuf) -> Option<((usize, usize), Vec<u8>)> {
+    let file = File::open(path).ok()?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().unwrap();
+    let mut bytes = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut bytes).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgba, "{:?} is not RGBA", path);
+    bytes.truncate(info.buffer_size());
+    Some(((info.width as usize, info.height as usize), bytes))
+}
+
+/**
+ * What the LTDC shows of the two layers in front of `background`.
+ */
+fn on_background(layer_1: &RgbaBuffer, layer_2: &RgbaBuffer, background: Color) -> RgbaBuffer {
+    let (width, height) = (layer_1.width(), layer_1.height());
+    let mut screen = RgbaBuffer::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let color = screenshot::composite(background, layer_1.pixel(x, y), layer_2.pixel(x, y));
+            screen.set_pixel(x, y, color);
+        }
+    }
+    screen
+}
+
+/**
+ * Compares `buffer` with the reference image `name`.png, or writes it with
+ * `UPDATE_GOLDEN` set.
+ */
+fn check(name: &str, buffer: &RgbaBuffer) {
+    let reference = path(&format!("{}.png", name));
+    let actual = path(&format!("{}.actual.png", name));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        write_png(&reference, buffer);
+        return;
+    }
+    let size = (buffer.width(), buffer.height());
+    let matches = match read_png(&reference) {
+        Some(expected) => expected == (size, rgba_bytes(buffer)),
+        None => false,
+    };
+    if !matches {
+        write_png(&actual, buffer);
+        panic!(
+            "{} differs from {:?} (or it is missing), the render is in {:?}",
+            name, reference, actual
+        );
+    }
+}
+
+#[test]
+fn pause_menu() {
+    let game = RgbaBuffer::new(LAYOUT_SIZE.0, LAYOUT_SIZE.1);
+    let mut buffer = RgbaBuffer::new(LAYOUT_SIZE.0, LAYOUT_SIZE.1);
+    let rules = Rules {
+        goal: Goal::Apples(10),
+        rounds: 3,
+    };
+    let options = render::Options {
+        smooth_movement: true,
+        arena: false,
+        table_top: false,
+        players: 2,
+        rules,
+        board: BoardSize::Normal,
+        theme: &theme::CLASSIC,
+    };
+    let menu = render::pause_menu(&options, LAYOUT_SIZE);
+    menu.draw(&mut buffer);
+    let background = options.theme.background;
+    check("pause_menu", &on_background(&game, &buffer, background));
+}
+
+#[test]
+fn welcome_screen() {
+    let mut buffer = RgbaBuffer::new(LAYOUT_SIZE.0, LAYOUT_SIZE.1);
+    render::draw_welcome_screen(&mut buffer);
+    render::draw_welcome_mouth(&mut buffer, true);
+    let menu = RgbaBuffer::new(LAYOUT_SIZE.0, LAYOUT_SIZE.1);
+    let background = theme::CLASSIC.background;
+    check("welcome_screen", &on_background(&buffer, &menu, background));
+}
+
+#[test]
+fn game_board() {
+    let theme = &theme::CLASSIC;
+    let cell_size = BoardSize::Normal.cell_size();
+    let mut buffer = RgbaBuffer::new(LAYOUT_SIZE.0, LAYOUT_SIZE.1);
+    render::draw_frame(&mut buffer, cell_size, theme);
+
+    // player 0 heads right and turns down, player 1 heads left
+    let body = |player, corner, rot| CellContent::Body {
+        player,
+        corner,
+        rot,
+    };
+    let cells = vec![
+        ((5, 5), CellContent::Tail {
+            player: 0,
+            rot: RotDirection::R0,
+        }),
+        ((6, 5), body(0, false, RotDirection::R0)),
+        ((7, 5), body(0, false, RotDirection::R0)),
+        ((8, 5), body(0, true, RotDirection::R0)),
+        ((8, 6), body(0, false, RotDirection::R90)),
+        ((8, 7), CellContent::Head {
+            player: 0,
+            rot: RotDirection::R270,
+            mouth_open: false,
+        }),
+        ((30, 15), CellContent::Tail {
+            player: 1,
+            rot: RotDirection::R180,
+        }),
+        ((29, 15), body(1, false, RotDirection::R0)),
+        ((28, 15), CellContent::Head {
+            player: 1,
+            rot: RotDirection::R0,
+            mouth_open: true,
+        }),
+        ((20, 10), CellContent::Apple {
+            sprite: &assets::APPLE,
+        }),
+    ];
+    for (cell, content) in cells {
+        render::draw_cell(&mut buffer, cell, cell_size, content, theme);
+    }
+    let menu = RgbaBuffer::new(LAYOUT_SIZE.0, LAYOUT_SIZE.1);
+    check("game_board", &on_background(&buffer, &menu, theme.background));
+}
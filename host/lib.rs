@@ -0,0 +1,55 @@
+//! The modules of the game that do not touch the hardware, built for the host
+//! so that their tests run with `cargo test` in this directory. The modules
+//! are included from `../src` as they are.
+
+#![allow(dead_code, unused_imports)]
+// lints of newer compilers about the style of the 2018 toolchain the game is
+// built with
+#![allow(unknown_lints, ellipsis_inclusive_range_patterns, mismatched_lifetime_syntaxes)]
+#![allow(deprecated)]
+
+extern crate core;
+#[cfg(test)]
+extern crate png;
+
+/// What the modules use from `alloc` on the board.
+mod alloc {
+    pub use std::string::String;
+    pub use std::vec::Vec;
+}
+
+#[path = "../src/animation.rs"]
+mod animation;
+#[path = "../src/assets.rs"]
+mod assets;
+#[path = "../src/camera.rs"]
+mod camera;
+#[path = "../src/canvas.rs"]
+mod canvas;
+#[path = "../src/font.rs"]
+mod font;
+#[path = "../src/mixer.rs"]
+mod mixer;
+#[path = "../src/music.rs"]
+mod music;
+#[path = "../src/render.rs"]
+mod render;
+#[path = "../src/rle.rs"]
+mod rle;
+#[path = "../src/rules.rs"]
+mod rules;
+#[path = "../src/screenshot.rs"]
+mod screenshot;
+#[path = "../src/sprite.rs"]
+mod sprite;
+#[path = "../src/theme.rs"]
+mod theme;
+#[path = "../src/touch_filter.rs"]
+mod touch_filter;
+#[path = "../src/ui.rs"]
+mod ui;
+
+#[cfg(test)]
+mod golden;
+#[cfg(test)]
+mod songs;
@@ -0,0 +1,103 @@
+//! Renders the first `PREVIEW` seconds of every song with the mixer of the
+//! game to a WAV file and checks the file. With `SONG_WAV_DIR` set, the files
+//! are copied to `<name>.wav` in that directory, so a song can be listened to
+//! without flashing the board.
+
+use mixer::{self, Mixer};
+use music;
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::process;
+
+/// (name of the WAV file, song), the same as `SONGS` in `build.rs`
+const SONGS: &[(&str, &str)] = &[("theme", music::THEME)];
+
+/// Seconds of every song that are played.
+const PREVIEW: usize = 30;
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&[value as u8, (value >> 8) as u8]);
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    push_u16(out, value as u16);
+    push_u16(out, (value >> 16) as u16);
+}
+
+/**
+ * A WAV file header for 16 bit stereo samples at the mixer's sample rate.
+ */
+fn wav_header(samples: usize) -> Vec<u8> {
+    let data_bytes = samples as u32 * 2;
+    let mut header = Vec::new();
+    header.extend_from_slice(b"RIFF");
+    push_u32(&mut header, 36 + data_bytes);
+    header.extend_from_slice(b"WAVEfmt ");
+    push_u32(&mut header, 16);
+    // PCM, two channels
+    push_u16(&mut header, 1);
+    push_u16(&mut header, 2);
+    push_u32(&mut header, mixer::SAMPLE_RATE);
+    push_u32(&mut header, mixer::SAMPLE_RATE * 4);
+    // bytes per frame, bits per sample
+    push_u16(&mut header, 4);
+    push_u16(&mut header, 16);
+    header.extend_from_slice(b"data");
+    push_u32(&mut header, data_bytes);
+    header
+}
+
+fn write_wav(path: &Path, samples: &[i16]) {
+    let mut wav = wav_header(samples.len());
+    for &sample in samples {
+        push_u16(&mut wav, sample as u16);
+    }
+    File::create(path)
+        .and_then(|mut file| file.write_all(&wav))
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+}
+
+/// Bytes of the header `wav_header` writes.
+const HEADER_BYTES: usize = 44;
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from(bytes[at])
+        | u32::from(bytes[at + 1]) << 8
+        | u32::from(bytes[at + 2]) << 16
+        | u32::from(bytes[at + 3]) << 24
+}
+
+#[test]
+fn songs_render_to_wav_files() {
+    let copy_to = env::var_os("SONG_WAV_DIR");
+    for &(name, song) in SONGS {
+        let mut mixer = Mixer::new();
+        mixer.play_music(song).unwrap();
+        let mut samples = vec![0; PREVIEW * mixer::SAMPLE_RATE as usize * 2];
+        mixer.fill(&mut samples);
+        assert!(samples.iter().any(|&sample| sample != 0), "song {} is silent", name);
+
+        let path = env::temp_dir().join(format!("multi_snake_{}_{}.wav", name, process::id()));
+        write_wav(&path, &samples);
+        let wav = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // 16 bit stereo
+        let data_bytes = PREVIEW * mixer::SAMPLE_RATE as usize * 2 * 2;
+        assert_eq!(wav.len(), HEADER_BYTES + data_bytes, "song {}", name);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32_at(&wav, 4) as usize, wav.len() - 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(&wav, 24), mixer::SAMPLE_RATE);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32_at(&wav, 40) as usize, data_bytes);
+        let first = i16::from(wav[HEADER_BYTES]) | i16::from(wav[HEADER_BYTES + 1]) << 8;
+        assert_eq!(first, samples[0]);
+
+        if let Some(ref dir) = copy_to {
+            fs::write(Path::new(dir).join(format!("{}.wav", name)), &wav).unwrap();
+        }
+    }
+}
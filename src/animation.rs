@@ -0,0 +1,212 @@
+//! Frame based animations driven by the game clock.
+//!
+//! An `Animation` does not draw anything itself, it only tells which frame is
+//! current. Call `tick` with the current time regularly and redraw whatever
+//! shows the animation when it returns `true`.
+
+/**
+ * One frame of an animation and how long it is shown in ms.
+ */
+pub struct Frame<T> {
+    pub value: T,
+    pub duration: usize,
+}
+
+/**
+ * What happens after the last frame.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum Playback {
+    /// start over with the first frame
+    Loop,
+    /// stop, `value` returns `None` afterwards
+    Once,
+}
+
+pub struct Animation<T: Copy + 'static> {
+    frames: &'static [Frame<T>],
+    playback: Playback,
+    started_at: usize,
+    current: usize,
+    running: bool,
+}
+
+impl<T: Copy + 'static> Animation<T> {
+    /**
+     * Creates a stopped animation, call `start` to play it.
+     */
+    pub fn new(frames: &'static [Frame<T>], playback: Playback) -> Animation<T> {
+        Animation {
+            frames,
+            playback,
+            started_at: 0,
+            current: 0,
+            running: false,
+        }
+    }
+
+    /**
+     * Plays the animation from its first frame, `now` in ms.
+     */
+    pub fn start(&mut self, now: usize) {
+        self.started_at = now;
+        self.current = 0;
+        self.running = !self.frames.is_empty();
+    }
+
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /**
+     * Value of the current frame, `None` if the animation is not running.
+     */
+    pub fn value(&self) -> Option<T> {
+        if self.running {
+            Some(self.frames[self.current].value)
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Advances the animation to `now` (ms). Returns `true` if the current
+     * frame changed or a one-shot animation ended, i.e. it has to be redrawn.
+     */
+    pub fn tick(&mut self, now: usize) -> bool {
+        if !self.running {
+            return false;
+        }
+        let total: usize = self.frames.iter().map(|frame| frame.duration).sum();
+        let mut elapsed = now.wrapping_sub(self.started_at);
+        if elapsed >= total {
+            match self.playback {
+                Playback::Loop => elapsed %= total.max(1),
+                Playback::Once => {
+                    self.running = false;
+                    return true;
+                }
+            }
+        }
+
+        let mut frame = 0;
+        while frame + 1 < self.frames.len() && elapsed >= self.frames[frame].duration {
+            elapsed -= self.frames[frame].duration;
+            frame += 1;
+        }
+        let changed = frame != self.current;
+        self.current = frame;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 350 ms in total.
+    static FRAMES: [Frame<char>; 3] = [
+        Frame { value: 'a', duration: 100 },
+        Frame { value: 'b', duration: 50 },
+        Frame { value: 'c', duration: 200 },
+    ];
+
+    fn started(playback: Playback, now: usize) -> Animation<char> {
+        let mut animation = Animation::new(&FRAMES, playback);
+        animation.start(now);
+        animation
+    }
+
+    #[test]
+    fn new_animation_is_stopped() {
+        let mut animation = Animation::new(&FRAMES, Playback::Loop);
+        assert!(!animation.is_running());
+        assert_eq!(animation.value(), None);
+        assert!(!animation.tick(1000));
+
+        static EMPTY: [Frame<char>; 0] = [];
+        let mut empty = Animation::new(&EMPTY, Playback::Loop);
+        empty.start(0);
+        assert!(!empty.is_running());
+        assert!(!empty.tick(10));
+    }
+
+    #[test]
+    fn frames_follow_their_durations() {
+        let mut animation = started(Playback::Once, 1000);
+        assert_eq!(animation.value(), Some('a'));
+        for &(now, changed, value) in &[
+            (1000, false, 'a'),
+            (1099, false, 'a'),
+            (1100, true, 'b'),
+            (1149, false, 'b'),
+            (1150, true, 'c'),
+            (1349, false, 'c'),
+        ] {
+            assert_eq!(animation.tick(now), changed, "at {}", now);
+            assert_eq!(animation.value(), Some(value), "at {}", now);
+        }
+    }
+
+    #[test]
+    fn late_ticks_skip_frames() {
+        let mut animation = started(Playback::Once, 0);
+        assert!(animation.tick(200));
+        assert_eq!(animation.value(), Some('c'));
+    }
+
+    #[test]
+    fn one_shot_ends_after_the_last_frame() {
+        let mut animation = started(Playback::Once, 1000);
+        assert!(animation.tick(1349));
+        assert_eq!(animation.value(), Some('c'));
+        assert!(animation.tick(1350));
+        assert!(!animation.is_running());
+        assert_eq!(animation.value(), None);
+        assert!(!animation.tick(1400));
+
+        animation.start(2000);
+        assert_eq!(animation.value(), Some('a'));
+        assert!(animation.tick(2500));
+        assert_eq!(animation.value(), None);
+    }
+
+    #[test]
+    fn loop_wraps_to_the_first_frame() {
+        let mut animation = started(Playback::Loop, 1000);
+        assert!(animation.tick(1150));
+        assert_eq!(animation.value(), Some('c'));
+        assert!(animation.tick(1350));
+        assert_eq!(animation.value(), Some('a'));
+        assert!(animation.tick(1450));
+        assert_eq!(animation.value(), Some('b'));
+        assert!(animation.tick(1000 + 3 * 350 + 160));
+        assert_eq!(animation.value(), Some('c'));
+        assert!(animation.is_running());
+    }
+
+    #[test]
+    fn clock_wrap_keeps_the_timing() {
+        let mut animation = started(Playback::Loop, usize::max_value() - 9);
+        assert!(!animation.tick(89));
+        assert!(animation.tick(90));
+        assert_eq!(animation.value(), Some('b'));
+    }
+
+    #[test]
+    fn stop_and_restart() {
+        let mut animation = started(Playback::Loop, 0);
+        animation.tick(160);
+        animation.stop();
+        assert_eq!(animation.value(), None);
+        assert!(!animation.tick(200));
+        animation.start(300);
+        assert_eq!(animation.value(), Some('a'));
+        assert!(animation.tick(400));
+        assert_eq!(animation.value(), Some('b'));
+    }
+}
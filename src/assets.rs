@@ -0,0 +1,7 @@
+//! Sprites generated from the bitmaps in `assets/` by `build.rs`.
+//! See the `ASSETS` table there to add a new one.
+
+use core::marker::PhantomData;
+use sprite::Sprite;
+
+include!(concat!(env!("OUT_DIR"), "/assets.rs"));
@@ -0,0 +1,199 @@
+//! The part of the world that is shown on the screen.
+
+/// Share of the remaining distance the camera moves per update, in 1/256.
+const SMOOTHING: usize = 64;
+
+/**
+ * Follows a target through a world larger than the screen. The target can
+ * move freely inside a deadzone around the center of the view; once it
+ * leaves it, the camera glides after it instead of jumping.
+ *
+ * All positions are in world pixels.
+ */
+pub struct Camera {
+    /// top left corner of the view
+    pub x: usize,
+    pub y: usize,
+    view: (usize, usize),
+    world: (usize, usize),
+    /// minimum distance of the target from the left/right and top/bottom
+    /// edge of the view
+    margin: (usize, usize),
+}
+
+impl Camera {
+    /**
+     * A camera showing `view` pixels of a `world` sized world. The deadzone
+     * covers the middle half of the view.
+     */
+    pub fn new(view: (usize, usize), world: (usize, usize)) -> Camera {
+        Camera {
+            x: 0,
+            y: 0,
+            view,
+            world,
+            margin: (view.0 / 4, view.1 / 4),
+        }
+    }
+
+    /**
+     * Moves the view so `target` is in its center.
+     */
+    pub fn center_on(&mut self, target: (usize, usize)) {
+        let (x, y) = (target.0 as isize, target.1 as isize);
+        self.x = clamp_axis(x - (self.view.0 / 2) as isize, self.view.0, self.world.0);
+        self.y = clamp_axis(y - (self.view.1 / 2) as isize, self.view.1, self.world.1);
+    }
+
+    /**
+     * Moves the view a bit towards keeping `target` inside the deadzone.
+     * Returns `true` if the view moved.
+     */
+    pub fn follow(&mut self, target: (usize, usize)) -> bool {
+        let x = follow_axis(self.x, target.0, self.margin.0, self.view.0, self.world.0);
+        let y = follow_axis(self.y, target.1, self.margin.1, self.view.1, self.world.1);
+        let moved = (x, y) != (self.x, self.y);
+        self.x = x;
+        self.y = y;
+        moved
+    }
+
+    /**
+     * Whether any part of the rectangle is on the screen.
+     */
+    pub fn is_visible(&self, x: usize, y: usize, width: usize, height: usize) -> bool {
+        x + width > self.x
+            && x < self.x + self.view.0
+            && y + height > self.y
+            && y < self.y + self.view.1
+    }
+}
+
+/// Keeps the view inside the world, a world smaller than the view stays at 0.
+fn clamp_axis(position: isize, view: usize, world: usize) -> usize {
+    let max = world.saturating_sub(view) as isize;
+    position.max(0).min(max) as usize
+}
+
+fn follow_axis(position: usize, target: usize, margin: usize, view: usize, world: usize) -> usize {
+    let wanted = if target < position + margin {
+        target as isize - margin as isize
+    } else if target + margin > position + view {
+        (target + margin) as isize - view as isize
+    } else {
+        return position;
+    };
+    let wanted = clamp_axis(wanted, view, world) as isize;
+    let distance = wanted - position as isize;
+    if distance.abs() as usize > view {
+        // the target wrapped around the world, gliding would cross all of it
+        return wanted as usize;
+    }
+    let mut step = distance * SMOOTHING as isize / 256;
+    if step == 0 {
+        step = distance.signum();
+    }
+    (position as isize + step) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 100x80 view of a 400x300 world, the deadzone margin is 25x20.
+    fn camera() -> Camera {
+        Camera::new((100, 80), (400, 300))
+    }
+
+    /// Follows `target` until the camera stops, returns the number of steps.
+    fn settle(camera: &mut Camera, target: (usize, usize)) -> usize {
+        let mut steps = 0;
+        while camera.follow(target) {
+            steps += 1;
+            assert!(steps < 100, "the camera does not settle");
+        }
+        steps
+    }
+
+    #[test]
+    fn target_in_the_deadzone_holds_the_camera() {
+        let mut camera = camera();
+        camera.center_on((200, 150));
+        assert_eq!((camera.x, camera.y), (150, 110));
+        for &target in &[(175, 130), (225, 170), (175, 170), (225, 130), (200, 150)] {
+            assert!(!camera.follow(target));
+            assert_eq!((camera.x, camera.y), (150, 110));
+        }
+        assert!(camera.follow((174, 150)));
+        assert!(camera.follow((226, 150)));
+    }
+
+    #[test]
+    fn smoothing_converges_on_the_deadzone_edge() {
+        let mut camera = camera();
+        camera.center_on((200, 150));
+        let target = (300, 150);
+
+        // a quarter of the remaining distance per update, at least a pixel
+        assert!(camera.follow(target));
+        assert_eq!(camera.x, 150 + 75 * 64 / 256);
+        assert_eq!(camera.y, 110);
+
+        let mut last = camera.x;
+        while camera.follow(target) {
+            assert!(camera.x > last && camera.x <= 225);
+            last = camera.x;
+        }
+        // the target ends up on the right edge of the deadzone
+        assert_eq!(camera.x, 300 + 25 - 100);
+        assert_eq!(settle(&mut camera, target), 0);
+
+        settle(&mut camera, (100, 40));
+        assert_eq!((camera.x, camera.y), (100 - 25, 40 - 20));
+    }
+
+    #[test]
+    fn target_far_away_is_jumped_to() {
+        let mut camera = camera();
+        // like a snake wrapping around the world
+        assert!(camera.follow((390, 10)));
+        assert_eq!(camera.x, 300);
+    }
+
+    #[test]
+    fn view_is_clamped_at_the_world_edges() {
+        let mut camera = camera();
+        camera.center_on((0, 0));
+        assert_eq!((camera.x, camera.y), (0, 0));
+        camera.center_on((399, 299));
+        assert_eq!((camera.x, camera.y), (300, 220));
+
+        camera.center_on((200, 150));
+        settle(&mut camera, (399, 299));
+        assert_eq!((camera.x, camera.y), (300, 220));
+        settle(&mut camera, (0, 0));
+        assert_eq!((camera.x, camera.y), (0, 0));
+        assert!(!camera.follow((5, 5)));
+    }
+
+    #[test]
+    fn world_smaller_than_the_view_stays_at_the_origin() {
+        let mut camera = Camera::new((100, 80), (50, 40));
+        camera.center_on((49, 39));
+        assert_eq!((camera.x, camera.y), (0, 0));
+        assert!(!camera.follow((49, 39)));
+        assert!(!camera.follow((0, 0)));
+    }
+
+    #[test]
+    fn visibility_of_rectangles() {
+        let mut camera = camera();
+        camera.center_on((200, 150));
+        assert!(camera.is_visible(150, 110, 1, 1));
+        assert!(camera.is_visible(249, 189, 1, 1));
+        assert!(camera.is_visible(140, 100, 11, 11));
+        assert!(!camera.is_visible(140, 100, 10, 10));
+        assert!(!camera.is_visible(250, 150, 10, 10));
+        assert!(!camera.is_visible(200, 190, 10, 10));
+    }
+}
@@ -0,0 +1,887 @@
+//! Drawing surfaces that do not depend on the board.
+//!
+//! Everything the game draws goes through the `Canvas` trait, which is
+//! implemented for the LTDC layers in `graphics` and for `RgbaBuffer`, a plain
+//! buffer in memory, so the drawing code can also run on a PC.
+
+use alloc::Vec;
+use rle;
+use sprite::{RotDirection, Sprite};
+
+/**
+ * An RGBA color, same layout as `lcd::Color`.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl Color {
+    pub fn rgb(red: u8, green: u8, blue: u8) -> Color {
+        Color::rgba(red, green, blue, 255)
+    }
+
+    pub fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Color {
+        Color {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    /**
+     * Color from `0xRRGGBB`, fully opaque.
+     */
+    pub fn from_hex(hex: u32) -> Color {
+        Color::rgb((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+    }
+
+    /**
+     * Perceived brightness (ITU-R BT.601), what an AL88 layer shows of the
+     * color.
+     */
+    pub fn luminance(&self) -> u8 {
+        ((77 * u32::from(self.red) + 150 * u32::from(self.green) + 29 * u32::from(self.blue) + 128)
+            >> 8) as u8
+    }
+
+    /**
+     * Fully transparent, shows the layers below.
+     */
+    pub fn transparent() -> Color {
+        Color::rgba(255, 255, 255, 0)
+    }
+}
+
+/**
+ * A sprite pixel format that can be converted to a color.
+ */
+pub trait ToColor: rle::Pixel {
+    fn to_color(self) -> Color;
+}
+
+impl ToColor for u32 {
+    /**
+     * ARGB8888
+     */
+    fn to_color(self) -> Color {
+        Color::rgba(
+            (self >> 16) as u8,
+            (self >> 8) as u8,
+            self as u8,
+            (self >> 24) as u8,
+        )
+    }
+}
+
+impl ToColor for u16 {
+    /**
+     * AL88
+     */
+    fn to_color(self) -> Color {
+        let luminance = self as u8;
+        Color::rgba(luminance, luminance, luminance, (self >> 8) as u8)
+    }
+}
+
+/**
+ * How the transparent pixels of a sprite are handled by `Canvas::blit`.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum Blend {
+    /// transparent pixels are skipped
+    SkipTransparent,
+    /// every pixel is written as fully opaque
+    Opaque,
+    /// transparent pixels are skipped, grey pixels are recolored: `TINT_BASE`
+    /// grey becomes the given color, darker and lighter greys are shaded
+    /// accordingly
+    Tint(Color),
+}
+
+/// The grey the snake sprites are drawn in, see `Blend::Tint`.
+pub const TINT_BASE: u8 = 121;
+
+/**
+ * The color a sprite pixel is drawn with, `None` if it is skipped.
+ */
+fn blend_pixel(color: Color, blend: Blend) -> Option<Color> {
+    match blend {
+        Blend::Opaque => Some(Color { alpha: 255, ..color }),
+        _ if color.alpha == 0 => None,
+        Blend::Tint(tint_color) => Some(tint(color, tint_color)),
+        Blend::SkipTransparent => Some(color),
+    }
+}
+
+fn tint(pixel: Color, color: Color) -> Color {
+    let shade = |channel: u8| {
+        (u32::from(channel) * u32::from(pixel.luminance()) / u32::from(TINT_BASE)).min(255) as u8
+    };
+    Color::rgba(shade(color.red), shade(color.green), shade(color.blue), pixel.alpha)
+}
+
+/**
+ * Something that can be drawn on.
+ */
+pub trait Canvas {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+
+    /**
+     * Sets a single pixel. Pixels outside of the canvas are ignored.
+     */
+    fn set_pixel(&mut self, x: usize, y: usize, color: Color);
+
+    /**
+     * Fills `len` pixels of row y, starting at x. This is the primitive the
+     * other shapes are built from, canvases with direct access to their
+     * memory should override it.
+     */
+    fn fill_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        for x in x..(x + len) {
+            self.set_pixel(x, y, color);
+        }
+    }
+
+    /**
+     * Fills a rectangle with a color.
+     */
+    fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Color) {
+        for y in y..(y + height) {
+            self.fill_span(x, y, width, color);
+        }
+    }
+
+    /**
+     * Draws a horizontal line from x,y to the right.
+     */
+    fn draw_hline(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        self.fill_span(x, y, len, color);
+    }
+
+    /**
+     * Draws a vertical line from x,y downwards.
+     */
+    fn draw_vline(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        for y in y..(y + len) {
+            self.set_pixel(x, y, color);
+        }
+    }
+
+    /**
+     * Draws the outline of a rectangle, one pixel wide.
+     */
+    fn draw_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Color) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.draw_hline(x, y, width, color);
+        self.draw_hline(x, y + height - 1, width, color);
+        self.draw_vline(x, y, height, color);
+        self.draw_vline(x + width - 1, y, height, color);
+    }
+
+    /**
+     * Draws a line between two points (Bresenham). The points may lie
+     * outside of the canvas.
+     */
+    fn draw_line(&mut self, from: (isize, isize), to: (isize, isize), color: Color) {
+        let dx = (to.0 - from.0).abs();
+        let dy = -(to.1 - from.1).abs();
+        let step_x = if from.0 < to.0 { 1 } else { -1 };
+        let step_y = if from.1 < to.1 { 1 } else { -1 };
+        let (mut x, mut y) = from;
+        let mut error = dx + dy;
+        loop {
+            set_pixel_signed(self, x, y, color);
+            if (x, y) == to {
+                break;
+            }
+            let error_2 = 2 * error;
+            if error_2 >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if error_2 <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /**
+     * Draws the outline of a circle (midpoint algorithm).
+     */
+    fn draw_circle(&mut self, center: (isize, isize), radius: isize, color: Color) {
+        for (x, y) in circle_octant(radius) {
+            for &(sx, sy) in &[(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)] {
+                set_pixel_signed(self, center.0 + sx, center.1 + sy, color);
+            }
+        }
+    }
+
+    /**
+     * Fills a circle.
+     */
+    fn fill_circle(&mut self, center: (isize, isize), radius: isize, color: Color) {
+        for (x, y) in circle_octant(radius) {
+            fill_span_signed(self, center.0 - x, center.1 + y, 2 * x + 1, color);
+            fill_span_signed(self, center.0 - x, center.1 - y, 2 * x + 1, color);
+            fill_span_signed(self, center.0 - y, center.1 + x, 2 * y + 1, color);
+            fill_span_signed(self, center.0 - y, center.1 - x, 2 * y + 1, color);
+        }
+    }
+
+    /**
+     * Draws the outline of a rectangle with rounded corners.
+     */
+    fn draw_rounded_rect(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        radius: usize,
+        color: Color,
+    ) {
+        let radius = radius.min(width / 2).min(height / 2);
+        if radius == 0 {
+            self.draw_rect(x, y, width, height, color);
+            return;
+        }
+        self.draw_hline(x + radius, y, width - 2 * radius, color);
+        self.draw_hline(x + radius, y + height - 1, width - 2 * radius, color);
+        self.draw_vline(x, y + radius, height - 2 * radius, color);
+        self.draw_vline(x + width - 1, y + radius, height - 2 * radius, color);
+
+        // corner centers
+        let (left, top) = ((x + radius) as isize, (y + radius) as isize);
+        let right = (x + width - 1 - radius) as isize;
+        let bottom = (y + height - 1 - radius) as isize;
+        for (cx, cy) in circle_octant(radius as isize) {
+            for &(sx, sy) in &[(cx, cy), (cy, cx)] {
+                set_pixel_signed(self, right + sx, bottom + sy, color);
+                set_pixel_signed(self, left - sx, bottom + sy, color);
+                set_pixel_signed(self, left - sx, top - sy, color);
+                set_pixel_signed(self, right + sx, top - sy, color);
+            }
+        }
+    }
+
+    /**
+     * Fills a rectangle with rounded corners.
+     */
+    fn fill_rounded_rect(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        radius: usize,
+        color: Color,
+    ) {
+        let radius = radius.min(width / 2).min(height / 2);
+        self.fill_rect(x, y + radius, width, height - 2 * radius, color);
+        if radius == 0 {
+            return;
+        }
+        let (left, top) = ((x + radius) as isize, (y + radius) as isize);
+        let bottom = (y + height - 1 - radius) as isize;
+        let inner = (width - 2 * radius) as isize;
+        for (cx, cy) in circle_octant(radius as isize) {
+            for &(sx, sy) in &[(cx, cy), (cy, cx)] {
+                fill_span_signed(self, left - sx, top - sy, inner + 2 * sx, color);
+                fill_span_signed(self, left - sx, bottom + sy, inner + 2 * sx, color);
+            }
+        }
+    }
+
+    /**
+     * Makes the whole canvas transparent.
+     */
+    fn clear(&mut self) {
+        let (width, height) = (self.width(), self.height());
+        self.fill_rect(0, 0, width, height, Color::transparent());
+    }
+
+    /**
+     * Draws a sprite with its top left corner at x,y, rotated clockwise.
+     */
+    fn blit<P: ToColor>(
+        &mut self,
+        sprite: &Sprite<P>,
+        x: usize,
+        y: usize,
+        rot: RotDirection,
+        blend: Blend,
+    ) {
+        for (i, j, pixel) in sprite.rotated_pixels(rot) {
+            if let Some(color) = blend_pixel(pixel.to_color(), blend) {
+                self.set_pixel(x + i, y + j, color);
+            }
+        }
+    }
+
+    /**
+     * Like `blit`, but the sprite is scaled by `scale` (numerator,
+     * denominator) with nearest neighbour sampling.
+     */
+    fn blit_scaled<P: ToColor>(
+        &mut self,
+        sprite: &Sprite<P>,
+        x: usize,
+        y: usize,
+        rot: RotDirection,
+        blend: Blend,
+        scale: (usize, usize),
+    ) {
+        let (num, den) = scale;
+        if num == den {
+            self.blit(sprite, x, y, rot, blend);
+            return;
+        }
+        for (i, j, pixel) in sprite.rotated_pixels(rot) {
+            // the pixel covers [i * num / den, (i + 1) * num / den) when
+            // scaled, nothing if it is dropped while shrinking
+            let (left, right) = (i * num / den, (i + 1) * num / den);
+            let (top, bottom) = (j * num / den, (j + 1) * num / den);
+            if left == right || top == bottom {
+                continue;
+            }
+            if let Some(color) = blend_pixel(pixel.to_color(), blend) {
+                self.fill_rect(x + left, y + top, right - left, bottom - top, color);
+            }
+        }
+    }
+}
+
+/**
+ * Sets a pixel that may lie outside of the canvas.
+ */
+fn set_pixel_signed<C: Canvas + ?Sized>(canvas: &mut C, x: isize, y: isize, color: Color) {
+    if x >= 0 && y >= 0 {
+        canvas.set_pixel(x as usize, y as usize, color);
+    }
+}
+
+/**
+ * Fills a span that may be partially outside of the canvas.
+ */
+fn fill_span_signed<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    x: isize,
+    y: isize,
+    len: isize,
+    color: Color,
+) {
+    let width = canvas.width() as isize;
+    let start = x.max(0);
+    let end = (x + len).min(width);
+    if y >= 0 && y < canvas.height() as isize && start < end {
+        canvas.fill_span(start as usize, y as usize, (end - start) as usize, color);
+    }
+}
+
+/**
+ * Points of the first octant of a circle around 0,0, from (radius, 0) to
+ * the diagonal. Mirroring them gives the whole circle.
+ */
+fn circle_octant(radius: isize) -> CircleOctant {
+    CircleOctant {
+        x: radius,
+        y: 0,
+        error: 1 - radius,
+    }
+}
+
+struct CircleOctant {
+    x: isize,
+    y: isize,
+    error: isize,
+}
+
+impl Iterator for CircleOctant {
+    type Item = (isize, isize);
+
+    fn next(&mut self) -> Option<(isize, isize)> {
+        if self.x < self.y {
+            return None;
+        }
+        let point = (self.x, self.y);
+        self.y += 1;
+        if self.error < 0 {
+            self.error += 2 * self.y + 1;
+        } else {
+            self.x -= 1;
+            self.error += 2 * (self.y - self.x) + 1;
+        }
+        Some(point)
+    }
+}
+
+/**
+ * Draws on a part of a larger, virtual canvas: everything is drawn in world
+ * coordinates and shifted by the position of the view, whatever falls outside
+ * of the underlying canvas is clipped.
+ */
+pub struct Viewport<'a, C: Canvas + 'a> {
+    canvas: &'a mut C,
+    /// top left corner of the view in world coordinates
+    x: usize,
+    y: usize,
+    world_width: usize,
+    world_height: usize,
+}
+
+impl<'a, C: Canvas> Viewport<'a, C> {
+    /**
+     * Shows `canvas` sized part of a `world` sized canvas, starting at
+     * `position`.
+     */
+    pub fn new(
+        canvas: &'a mut C,
+        position: (usize, usize),
+        world: (usize, usize),
+    ) -> Viewport<'a, C> {
+        let ((x, y), (world_width, world_height)) = (position, world);
+        Viewport {
+            canvas,
+            x,
+            y,
+            world_width,
+            world_height,
+        }
+    }
+}
+
+impl<'a, C: Canvas> Canvas for Viewport<'a, C> {
+    fn width(&self) -> usize {
+        self.world_width
+    }
+
+    fn height(&self) -> usize {
+        self.world_height
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x >= self.x && y >= self.y {
+            self.canvas.set_pixel(x - self.x, y - self.y, color);
+        }
+    }
+
+    fn fill_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        if y < self.y || x + len <= self.x {
+            return;
+        }
+        let start = x.max(self.x);
+        self.canvas
+            .fill_span(start - self.x, y - self.y, x + len - start, color);
+    }
+
+    /**
+     * Clears what is on the screen.
+     */
+    fn clear(&mut self) {
+        self.canvas.clear();
+    }
+}
+
+/**
+ * Draws rotated onto another canvas, e.g. upside down for a player on the
+ * other side of the screen. Points drawn at x,y end up where
+ * `RotDirection::rotate_point` puts them, `to_canvas_point` maps back, e.g.
+ * touches.
+ */
+pub struct Rotated<'a, C: Canvas + 'a> {
+    canvas: &'a mut C,
+    rot: RotDirection,
+}
+
+impl<'a, C: Canvas> Rotated<'a, C> {
+    pub fn new(canvas: &'a mut C, rot: RotDirection) -> Rotated<'a, C> {
+        Rotated {
+            canvas,
+            rot,
+        }
+    }
+
+    /**
+     * The point of this canvas that is shown at x,y of the underlying one.
+     */
+    pub fn to_canvas_point(&self, point: (usize, usize)) -> (usize, usize) {
+        let size = (self.canvas.width(), self.canvas.height());
+        self.rot.inverse().rotate_point(point, size)
+    }
+}
+
+impl<'a, C: Canvas> Canvas for Rotated<'a, C> {
+    fn width(&self) -> usize {
+        match self.rot {
+            RotDirection::R0 | RotDirection::R180 => self.canvas.width(),
+            RotDirection::R90 | RotDirection::R270 => self.canvas.height(),
+        }
+    }
+
+    fn height(&self) -> usize {
+        match self.rot {
+            RotDirection::R0 | RotDirection::R180 => self.canvas.height(),
+            RotDirection::R90 | RotDirection::R270 => self.canvas.width(),
+        }
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.width() && y < self.height() {
+            let size = (self.width(), self.height());
+            let (x, y) = self.rot.rotate_point((x, y), size);
+            self.canvas.set_pixel(x, y, color);
+        }
+    }
+
+    fn fill_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        let (width, height) = (self.width(), self.height());
+        if x >= width || y >= height {
+            return;
+        }
+        let len = len.min(width - x);
+        match self.rot {
+            // rows stay rows
+            RotDirection::R0 => self.canvas.fill_span(x, y, len, color),
+            RotDirection::R180 => {
+                self.canvas
+                    .fill_span(width - x - len, height - 1 - y, len, color)
+            }
+            RotDirection::R90 | RotDirection::R270 => for i in x..x + len {
+                self.set_pixel(i, y, color);
+            },
+        }
+    }
+
+    fn clear(&mut self) {
+        self.canvas.clear();
+    }
+}
+
+/**
+ * A canvas in memory, e.g. to render screens on a PC and compare them with
+ * reference images.
+ */
+pub struct RgbaBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl RgbaBuffer {
+    /**
+     * Creates a fully transparent buffer.
+     */
+    pub fn new(width: usize, height: usize) -> RgbaBuffer {
+        RgbaBuffer {
+            width,
+            height,
+            pixels: vec![Color::transparent(); width * height],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    /**
+     * All pixels, row by row.
+     */
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+}
+
+impl Canvas for RgbaBuffer {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    fn fill_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        if y >= self.height || x >= self.width {
+            return;
+        }
+        let start = y * self.width + x;
+        let end = start + len.min(self.width - x);
+        for pixel in &mut self.pixels[start..end] {
+            *pixel = color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::marker::PhantomData;
+
+    const INK: Color = Color {
+        red: 255,
+        green: 0,
+        blue: 0,
+        alpha: 255,
+    };
+
+    /// The points set to `INK`, row by row.
+    fn inked(buffer: &RgbaBuffer) -> Vec<(usize, usize)> {
+        let mut points = Vec::new();
+        for y in 0..buffer.height() {
+            for x in 0..buffer.width() {
+                if buffer.pixel(x, y) == INK {
+                    points.push((x, y));
+                }
+            }
+        }
+        points
+    }
+
+    fn line(from: (isize, isize), to: (isize, isize)) -> Vec<(usize, usize)> {
+        let mut buffer = RgbaBuffer::new(20, 20);
+        buffer.draw_line(from, to, INK);
+        inked(&buffer)
+    }
+
+    #[test]
+    fn line_hits_both_ends_in_every_octant() {
+        let center = (10, 10);
+        let ends = [(18, 13), (13, 18), (7, 18), (2, 13), (2, 7), (7, 2), (13, 2), (18, 7)];
+        for &end in ends.iter() {
+            let points = line(center, end);
+            assert!(points.contains(&(10, 10)), "{:?}", end);
+            assert!(points.contains(&(end.0 as usize, end.1 as usize)), "{:?}", end);
+            // one pixel per step along the longer axis
+            let steps = (end.0 - center.0).abs().max((end.1 - center.1).abs()) as usize;
+            assert_eq!(points.len(), steps + 1, "{:?}", end);
+            // drawn backwards it ends where it started
+            let backwards = line(end, center);
+            assert!(backwards.contains(&(10, 10)), "{:?}", end);
+            assert_eq!(backwards.len(), steps + 1, "{:?}", end);
+        }
+    }
+
+    #[test]
+    fn straight_and_diagonal_lines() {
+        assert_eq!(line((2, 5), (6, 5)), vec![(2, 5), (3, 5), (4, 5), (5, 5), (6, 5)]);
+        assert_eq!(line((4, 1), (4, 3)), vec![(4, 1), (4, 2), (4, 3)]);
+        assert_eq!(line((1, 1), (3, 3)), vec![(1, 1), (2, 2), (3, 3)]);
+        assert_eq!(line((5, 5), (5, 5)), vec![(5, 5)]);
+    }
+
+    #[test]
+    fn line_is_clipped() {
+        assert_eq!(line((-3, 2), (2, 2)), vec![(0, 2), (1, 2), (2, 2)]);
+        assert_eq!(line((17, 0), (25, 0)), vec![(17, 0), (18, 0), (19, 0)]);
+    }
+
+    #[test]
+    fn circle_is_symmetric() {
+        let mut buffer = RgbaBuffer::new(21, 21);
+        buffer.draw_circle((10, 10), 7, INK);
+        let points = inked(&buffer);
+        for &(x, y) in &points {
+            for &mirrored in &[(20 - x, y), (x, 20 - y), (y, x)] {
+                assert!(points.contains(&mirrored), "{:?} of {:?}", mirrored, (x, y));
+            }
+        }
+        for &extreme in &[(17, 10), (3, 10), (10, 17), (10, 3)] {
+            assert!(points.contains(&extreme));
+        }
+        assert!(points.iter().all(|&(x, y)| {
+            let distance = (x as f64 - 10.0).hypot(y as f64 - 10.0);
+            (distance - 7.0).abs() < 1.0
+        }));
+    }
+
+    #[test]
+    fn filled_circle_covers_the_outline() {
+        let (mut outline, mut filled) = (RgbaBuffer::new(21, 21), RgbaBuffer::new(21, 21));
+        outline.draw_circle((10, 10), 6, INK);
+        filled.fill_circle((10, 10), 6, INK);
+        let filled = inked(&filled);
+        assert!(inked(&outline).iter().all(|point| filled.contains(point)));
+        assert!(filled.contains(&(10, 10)));
+        assert!(!filled.contains(&(4, 4)));
+    }
+
+    #[test]
+    fn circle_is_clipped() {
+        let mut buffer = RgbaBuffer::new(10, 10);
+        buffer.fill_circle((0, 0), 4, INK);
+        buffer.draw_circle((9, 9), 20, INK);
+        let points = inked(&buffer);
+        assert!(points.contains(&(0, 0)) && points.contains(&(4, 0)));
+        assert!(!points.contains(&(5, 0)));
+    }
+
+    #[test]
+    fn rounded_rect_corners() {
+        let mut buffer = RgbaBuffer::new(30, 20);
+        buffer.fill_rounded_rect(2, 3, 20, 10, 4, INK);
+        let points = inked(&buffer);
+        // the corners are cut off, the edges between them are straight
+        for &corner in &[(2, 3), (21, 3), (2, 12), (21, 12)] {
+            assert!(!points.contains(&corner), "{:?}", corner);
+        }
+        for &edge in &[(6, 3), (17, 3), (2, 7), (21, 8), (6, 12), (17, 12), (12, 8)] {
+            assert!(points.contains(&edge), "{:?}", edge);
+        }
+        // nothing outside of the rectangle
+        assert!(points.iter().all(|&(x, y)| x >= 2 && x < 22 && y >= 3 && y < 13));
+        // mirrored left to right and top to bottom
+        for &(x, y) in &points {
+            assert!(points.contains(&(23 - x, y)) && points.contains(&(x, 15 - y)));
+        }
+
+        let mut outline = RgbaBuffer::new(30, 20);
+        outline.draw_rounded_rect(2, 3, 20, 10, 4, INK);
+        let outline = inked(&outline);
+        assert!(outline.iter().all(|point| points.contains(point)));
+        assert!(!outline.contains(&(12, 8)) && !outline.contains(&(2, 3)));
+    }
+
+    #[test]
+    fn rounded_rect_radius_is_limited() {
+        let (mut rounded, mut square) = (RgbaBuffer::new(10, 10), RgbaBuffer::new(10, 10));
+        rounded.fill_rounded_rect(0, 0, 6, 4, 10, INK);
+        square.fill_rounded_rect(0, 0, 6, 4, 0, INK);
+        assert_eq!(inked(&square).len(), 24);
+        assert!(inked(&rounded).len() < 24);
+        assert!(inked(&rounded).contains(&(3, 0)));
+    }
+
+    #[test]
+    fn shapes_are_clipped_at_the_edges() {
+        let mut buffer = RgbaBuffer::new(8, 6);
+        buffer.fill_rect(5, 4, 10, 10, INK);
+        buffer.fill_rounded_rect(6, 0, 10, 3, 1, INK);
+        buffer.draw_rect(0, 0, 20, 20, INK);
+        let points = inked(&buffer);
+        assert!(points.contains(&(7, 5)) && points.contains(&(5, 4)));
+        assert!(points.contains(&(7, 1)));
+        assert_eq!(buffer.pixel(4, 3), Color::transparent());
+    }
+
+    /// A 2x2 sprite, red green on top of blue and a transparent pixel.
+    fn sprite() -> Sprite<u32> {
+        let transparent = <u32 as rle::Pixel>::TRANSPARENT;
+        let pixels = [0xffff_0000, 0xff00_ff00, 0xff00_00ff, transparent];
+        let data = rle::encode(&pixels, <u32 as rle::Pixel>::BYTES, transparent);
+        Sprite {
+            width: 2,
+            height: 2,
+            data: Box::leak(data.into_boxed_slice()),
+            pixel: PhantomData,
+        }
+    }
+
+    #[test]
+    fn blit_scaled_up() {
+        let mut buffer = RgbaBuffer::new(8, 8);
+        let sprite = sprite();
+        buffer.blit_scaled(&sprite, 1, 1, RotDirection::R0, Blend::SkipTransparent, (3, 2));
+        // every pixel becomes 1 or 2 pixels wide and high: 0..1, 1..3
+        let red = Color::rgb(255, 0, 0);
+        let (green, blue) = (Color::rgb(0, 255, 0), Color::rgb(0, 0, 255));
+        assert_eq!(buffer.pixel(1, 1), red);
+        assert_eq!(buffer.pixel(2, 1), green);
+        assert_eq!(buffer.pixel(3, 1), green);
+        assert_eq!(buffer.pixel(1, 2), blue);
+        assert_eq!(buffer.pixel(1, 3), blue);
+        assert_eq!(buffer.pixel(3, 3), Color::transparent());
+        assert_eq!(buffer.pixel(4, 1), Color::transparent());
+    }
+
+    #[test]
+    fn blit_scaled_down_and_rotated() {
+        let mut buffer = RgbaBuffer::new(4, 4);
+        let sprite = sprite();
+        // halved, only the last pixel of every two is kept, white where the
+        // transparent one is drawn opaque
+        buffer.blit_scaled(&sprite, 0, 0, RotDirection::R0, Blend::Opaque, (1, 2));
+        assert_eq!(buffer.pixel(0, 0), Color::rgb(255, 255, 255));
+        assert_eq!(buffer.pixel(1, 0), Color::transparent());
+
+        let mut buffer = RgbaBuffer::new(4, 4);
+        buffer.blit_scaled(&sprite, 0, 0, RotDirection::R90, Blend::SkipTransparent, (1, 1));
+        // turned clockwise: blue red on top of the transparent pixel and green
+        assert_eq!(buffer.pixel(0, 0), Color::rgb(0, 0, 255));
+        assert_eq!(buffer.pixel(1, 0), Color::rgb(255, 0, 0));
+        assert_eq!(buffer.pixel(1, 1), Color::rgb(0, 255, 0));
+        assert_eq!(buffer.pixel(0, 1), Color::transparent());
+    }
+
+    #[test]
+    fn viewport_shifts_and_clips() {
+        let mut buffer = RgbaBuffer::new(10, 10);
+        {
+            let mut view = Viewport::new(&mut buffer, (20, 30), (100, 100));
+            assert_eq!((view.width(), view.height()), (100, 100));
+            view.set_pixel(21, 32, INK);
+            // left of and above the view
+            view.set_pixel(19, 35, INK);
+            view.set_pixel(25, 29, INK);
+            // starts left of the view and ends in it
+            view.fill_span(15, 35, 8, INK);
+            view.fill_span(10, 36, 5, INK);
+        }
+        assert_eq!(inked(&buffer), vec![(1, 2), (0, 5), (1, 5), (2, 5)]);
+    }
+
+    #[test]
+    fn rotated_maps_points() {
+        for &rot in &[RotDirection::R0, RotDirection::R90, RotDirection::R180, RotDirection::R270] {
+            let mut buffer = RgbaBuffer::new(6, 4);
+            let expected = {
+                let mut rotated = Rotated::new(&mut buffer, rot);
+                rotated.set_pixel(1, 0, INK);
+                let size = (rotated.width(), rotated.height());
+                let point = rot.rotate_point((1, 0), size);
+                assert_eq!(rotated.to_canvas_point(point), (1, 0));
+                point
+            };
+            assert_eq!(inked(&buffer), vec![expected], "{:?}", rot);
+        }
+        let mut buffer = RgbaBuffer::new(6, 4);
+        {
+            let rotated = Rotated::new(&mut buffer, RotDirection::R90);
+            assert_eq!((rotated.width(), rotated.height()), (4, 6));
+        }
+        // upside down, the top left corner is the bottom right one
+        Rotated::new(&mut buffer, RotDirection::R180).set_pixel(0, 0, INK);
+        assert_eq!(inked(&buffer), vec![(5, 3)]);
+    }
+
+    #[test]
+    fn rotated_spans() {
+        for &rot in &[RotDirection::R0, RotDirection::R90, RotDirection::R180, RotDirection::R270] {
+            let (mut spans, mut pixels) = (RgbaBuffer::new(6, 4), RgbaBuffer::new(6, 4));
+            Rotated::new(&mut spans, rot).fill_span(1, 1, 10, INK);
+            {
+                let mut rotated = Rotated::new(&mut pixels, rot);
+                let width = rotated.width();
+                for x in 1..width {
+                    rotated.set_pixel(x, 1, INK);
+                }
+            }
+            // clipped at the edge and the same as pixel by pixel
+            assert_eq!(inked(&spans), inked(&pixels), "{:?}", rot);
+        }
+    }
+}
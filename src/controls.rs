@@ -0,0 +1,124 @@
+//! Touch control zones of the players.
+//!
+//! Every player steers in a zone of the screen: a touch on the left part of
+//! it turns their snake left, on the right part right and in between pauses
+//! the game. In table-top mode the zones of the players on the far side are
+//! rotated by 180°, so left and right are seen from where they sit.
+
+use alloc::Vec;
+use sprite::RotDirection;
+use ui::Rect;
+
+/// Players that can steer at the same time, one per corner of the screen.
+pub const MAX_PLAYERS: usize = 4;
+
+/**
+ * What a touch in a zone does.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Control {
+    TurnLeft,
+    TurnRight,
+    Pause,
+}
+
+/**
+ * The part of the screen a player touches to steer.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Zone {
+    pub player: usize,
+    /// in screen coordinates
+    pub area: Rect,
+    /// how the player sees the screen, `R0` or `R180`
+    pub side: RotDirection,
+}
+
+impl Zone {
+    pub fn new(player: usize, area: Rect, side: RotDirection) -> Zone {
+        Zone {
+            player: player,
+            area: area,
+            side: side,
+        }
+    }
+
+    /**
+     * The area as seen from the side of the player, i.e. in the coordinates
+     * of a `Rotated` canvas with the same rotation.
+     */
+    pub fn view_area(&self, (width, height): (usize, usize)) -> Rect {
+        let area = self.area;
+        match self.side {
+            RotDirection::R180 => Rect::new(
+                width - area.x - area.width,
+                height - area.y - area.height,
+                area.width,
+                area.height,
+            ),
+            _ => area,
+        }
+    }
+
+    /**
+     * The control touched at `point`, `None` if it is outside of the zone.
+     * The outer 5/24 of the zone on each side steer (100 pixels of a zone as
+     * wide as the 480 pixel panel), the rest pauses.
+     */
+    pub fn control(&self, point: (usize, usize), screen: (usize, usize)) -> Option<Control> {
+        if !self.area.contains(point) {
+            return None;
+        }
+        let view = self.view_area(screen);
+        let (x, _) = self.side.inverse().rotate_point(point, screen);
+        let steering = view.width * 5 / 24;
+        let x = x - view.x;
+        Some(if x < steering {
+            Control::TurnLeft
+        } else if x >= view.width - steering {
+            Control::TurnRight
+        } else {
+            Control::Pause
+        })
+    }
+}
+
+/**
+ * Splits a screen of the given size into the zones of `players` players.
+ * One player steers on the whole screen, two in the lower and upper half and
+ * up to four in the corners, starting bottom left and alternating between
+ * the near and the far side. In table-top mode a single player gets the
+ * upper half rotated as well, so they can play from either side.
+ */
+pub fn zones(players: usize, table_top: bool, (width, height): (usize, usize)) -> Vec<Zone> {
+    let far = if table_top {
+        RotDirection::R180
+    } else {
+        RotDirection::R0
+    };
+    let near = RotDirection::R0;
+    let (half_width, half_height) = (width / 2, height / 2);
+    let top = Rect::new(0, 0, width, half_height);
+    let bottom = Rect::new(0, half_height, width, height - half_height);
+    match players {
+        0 | 1 if !table_top => vec![Zone::new(0, Rect::new(0, 0, width, height), near)],
+        0 | 1 => vec![Zone::new(0, bottom, near), Zone::new(0, top, far)],
+        2 => vec![Zone::new(0, bottom, near), Zone::new(1, top, far)],
+        _ => {
+            let right_width = width - half_width;
+            let bottom_height = height - half_height;
+            let corners = [
+                (Rect::new(0, half_height, half_width, bottom_height), near),
+                (Rect::new(half_width, 0, right_width, half_height), far),
+                (Rect::new(half_width, half_height, right_width, bottom_height), near),
+                (Rect::new(0, 0, half_width, half_height), far),
+            ];
+            corners
+                .iter()
+                .take(players.min(MAX_PLAYERS))
+                .enumerate()
+                .map(|(player, &(area, side))| Zone::new(player, area, side))
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use core::ptr;
+
+/// Number of frames between two reports.
+const REPORT_INTERVAL: usize = 100;
+
+/// Core clock set up by `system_clock::init`, in cycles per µs.
+const CYCLES_PER_US: u32 = 216;
+
+/// Debug exception and monitor control register.
+const DEMCR: usize = 0xe000_edfc;
+/// Enables the DWT unit.
+const DEMCR_TRCENA: u32 = 1 << 24;
+/// DWT control register.
+const DWT_CTRL: usize = 0xe000_1000;
+/// Enables the cycle counter.
+const CTRL_CYCCNTENA: u32 = 1;
+/// DWT cycle count register, counts core clock cycles and wraps around.
+const DWT_CYCCNT: usize = 0xe000_1004;
+/// DWT lock access register, the Cortex-M7 ignores writes to the DWT until
+/// it is unlocked.
+const DWT_LAR: usize = 0xe000_1fb0;
+const LAR_UNLOCK: u32 = 0xc5ac_ce55;
+
+/**
+ * Measures how long drawing a frame takes, separately for full redraws and
+ * for frames that only redraw the changed cells.\n
+ * Build with `--features frame-timing` to print the numbers over semihosting
+ * every `REPORT_INTERVAL` frames. The time is taken with the cycle counter of
+ * the DWT, the ms of the system clock are too coarse for a partial redraw.
+ */
+pub struct FrameTimer {
+    started_at: u32,
+    full: FrameStats,
+    partial: FrameStats,
+}
+
+/**
+ * Accumulated draw times in core clock cycles.
+ */
+#[derive(Default)]
+struct FrameStats {
+    frames: usize,
+    total: u64,
+    max: u32,
+}
+
+impl FrameStats {
+    fn add(&mut self, cycles: u32) {
+        self.frames += 1;
+        self.total += u64::from(cycles);
+        if cycles > self.max {
+            self.max = cycles;
+        }
+    }
+}
+
+impl FrameTimer {
+    /**
+     * Starts the cycle counter.
+     */
+    pub fn new() -> FrameTimer {
+        unsafe {
+            let demcr = ptr::read_volatile(DEMCR as *const u32);
+            ptr::write_volatile(DEMCR as *mut u32, demcr | DEMCR_TRCENA);
+            ptr::write_volatile(DWT_LAR as *mut u32, LAR_UNLOCK);
+            let ctrl = ptr::read_volatile(DWT_CTRL as *const u32);
+            ptr::write_volatile(DWT_CTRL as *mut u32, ctrl | CTRL_CYCCNTENA);
+        }
+        FrameTimer {
+            started_at: 0,
+            full: FrameStats::default(),
+            partial: FrameStats::default(),
+        }
+    }
+
+    /**
+     * Call before drawing a frame.
+     */
+    pub fn start(&mut self) {
+        self.started_at = cycles();
+    }
+
+    /**
+     * Call after drawing a frame.
+     */
+    pub fn stop(&mut self, full_redraw: bool) {
+        // a frame takes far less than the 19 s until the counter wraps
+        let cycles = cycles().wrapping_sub(self.started_at);
+        if full_redraw {
+            self.full.add(cycles);
+        } else {
+            self.partial.add(cycles);
+        }
+        if (self.full.frames + self.partial.frames) % REPORT_INTERVAL == 0 {
+            self.report();
+        }
+    }
+
+    #[cfg(feature = "frame-timing")]
+    fn report(&self) {
+        let us = |cycles: u64| cycles / u64::from(CYCLES_PER_US);
+        for &(name, stats) in &[("full", &self.full), ("partial", &self.partial)] {
+            if stats.frames > 0 {
+                hprintln!(
+                    "{} redraw: {} frames, avg {} us, max {} us",
+                    name,
+                    stats.frames,
+                    us(stats.total / stats.frames as u64),
+                    us(u64::from(stats.max))
+                );
+            }
+        }
+    }
+
+    #[cfg(not(feature = "frame-timing"))]
+    fn report(&self) {}
+}
+
+fn cycles() -> u32 {
+    unsafe { ptr::read_volatile(DWT_CYCCNT as *const u32) }
+}
@@ -2,16 +2,37 @@ extern crate arrayvec;
 extern crate r0;
 extern crate stm32f7_discovery as stm32f7; // initialization routines for .data and .bss
 
+use alloc::String;
 use alloc::Vec;
+use animation::{Animation, Playback};
+use assets;
+use camera::Camera;
+use canvas::{Canvas, Color, Rotated, Viewport};
+use controls::{self, Control, Zone, MAX_PLAYERS};
+use frame_timer;
 use graphics;
+use graphics::RotDirection;
+use mixer;
+use music;
 use random;
-use stm32f7::{lcd, system_clock, touch};
+use render;
+use scheduler;
+use semihosting;
+use settings;
+use sound::{self, Sound};
+use theme::{self, Theme};
+use touch_filter::{Calibration, TouchFilter};
+use touchscreen::TouchScreen;
+use render::{BoardSize, MenuAction};
+use rules::{self, Goal, Match, Outcome, Rules};
+use sprite::Sprite;
+use stm32f7::system_clock;
+use transition::{Effect, Phase, Transition};
+use ui::{Event, Menu};
 
 use super::HEIGHT;
 use super::WIDTH;
 
-const GRID_BLOCK_SIZE: usize = 10;
-
 /**
  * Contains all necessary state information of a game.
  */
@@ -19,13 +40,101 @@ pub struct Game {
     pub graphics: graphics::Graphics,
     pub random_gen: random::Random,
     grid: Vec<Vec<Tile>>,
-    i2c_3: stm32f7::i2c::I2C,
-    pub snake_head_position: (usize, usize),
-    pub snake_body_position: Vec<(usize, usize)>,
-    snake_tail_position: (usize, usize),
-    former_snake_tail: (usize, usize),
+    touch_screen: TouchScreen,
+    /// `None` if the codec could not be set up
+    sound: Option<Sound>,
+    /// there were fingers on the screen when it was last read, or reading it
+    /// failed
+    touching: bool,
+    /// the warning that the touch screen does not answer is shown
+    touch_warning: bool,
+    /// turns the points read into presses and taps
+    touch_filter: TouchFilter,
+    /// corrects the points read, set on the calibration screen and kept
+    /// through restarts by `settings`
+    pub calibration: Calibration,
+    /// one snake per player, the index is the player
+    snakes: Vec<Snake>,
     apple_position: (usize, usize),
-    pub apple_counter: usize,
+    dirty_cells: Vec<(usize, usize)>,
+    full_redraw: bool,
+    frame_timer: frame_timer::FrameTimer,
+    apple_animation: Animation<&'static Sprite<u32>>,
+    /// size of the grid in cells, larger than the screen in arena mode
+    grid_width: usize,
+    grid_height: usize,
+    /// size of a cell in pixels
+    cell_size: usize,
+    /// preset for the cell size, takes effect with `reset`
+    pub board: BoardSize,
+    /// play in a world of `ARENA_SCREENS` screens, takes effect with `reset`
+    pub arena: bool,
+    camera: Camera,
+    minimap: render::Minimap,
+    minimap_dirty: bool,
+    /// the score has to be drawn again
+    hud_dirty: bool,
+    /// players sit on both sides of the screen, see `controls::zones`
+    pub table_top: bool,
+    /// side of the player who touched the screen last, menus face it
+    side: RotDirection,
+    /// number of snakes, takes effect with `reset`
+    pub players: usize,
+    /// where the players touch to steer their snakes
+    zones: Vec<Zone>,
+    /// takes effect with the next match
+    pub rules: Rules,
+    /// rounds won so far
+    current_match: Match,
+    round_started_at: usize,
+    /// seconds left in the round as last shown, with a time limit
+    shown_seconds_left: Option<usize>,
+    /// colors of everything that is drawn
+    pub theme: &'static Theme,
+    /// draw head and tail in between cells while the snake moves
+    pub smooth_movement: bool,
+    step_started_at: usize,
+    step_duration: usize,
+    /// movement progress of the last drawn frame, see `render::interpolate`
+    drawn_progress: u32,
+    transition: Option<Transition>,
+    /// what to show once the running transition has hidden the old screen
+    screen_change: ScreenChange,
+    /// number of screenshots taken, used for the file names
+    screenshots: usize,
+    /// two fingers were on the screen at the last tick
+    screenshot_touch: bool,
+}
+
+/// Size of the world in arena mode, in screens.
+const ARENA_SCREENS: (usize, usize) = (3, 3);
+
+/// One endless round, the game as it has always been.
+const DEFAULT_RULES: Rules = Rules {
+    goal: Goal::Endless,
+    rounds: 1,
+};
+
+/// Duration of each half of a screen transition in ms.
+const TRANSITION_DURATION: usize = 300;
+
+/// Tick duration in ms at the start of a game, the music plays at the tempo
+/// of the song then and speeds up with the snakes.
+const MUSIC_TICK: usize = 200;
+
+/**
+ * Screen changes that happen in the middle of a transition.
+ */
+#[derive(Clone, Copy)]
+enum ScreenChange {
+    /// welcome screen to game
+    StartGame,
+    /// from a menu to a fresh game, a new match starts
+    NewGame,
+    /// from the scoreboard to the next round of the match
+    NextRound,
+    /// close the menu and continue the game
+    Resume,
 }
 
 /**
@@ -35,11 +144,13 @@ pub struct Game {
 enum Tile {
     Empty,
     SnakeHead(Direction),
-    SnakeBody(Direction),
+    /// direction the snake moved when entering and when leaving the cell
+    SnakeBody(Direction, Direction),
+    /// direction of the next segment
     SnakeTail(Direction),
     Apple,
 }
-#[derive(PartialEq, Clone)]
+#[derive(PartialEq, Clone, Copy)]
 enum Direction {
     up,
     down,
@@ -47,30 +158,83 @@ enum Direction {
     right,
 }
 
+/**
+ * A snake and the score of the player steering it.
+ */
+struct Snake {
+    head: (usize, usize),
+    body: Vec<(usize, usize)>,
+    tail: (usize, usize),
+    former_tail: (usize, usize),
+    former_tail_tile: Tile,
+    /// where the head is going
+    direction: Direction,
+    /// apples eaten in this game
+    score: usize,
+    /// false once it bit itself or another snake
+    alive: bool,
+    /// chomps after eating an apple
+    head_animation: Animation<bool>,
+}
+
 impl Game {
     /**
      * Create a new game.
      */
     pub fn new(
         graphics: graphics::Graphics,
-        i2c_3: stm32f7::i2c::I2C,
+        touch_screen: TouchScreen,
         random_gen: random::Random,
+        sound: Option<Sound>,
     ) -> Game {
-        let game_width = WIDTH / GRID_BLOCK_SIZE;
-        let game_height = HEIGHT / GRID_BLOCK_SIZE;
+        let board = BoardSize::Normal;
+        let game_width = WIDTH / board.cell_size();
+        let game_height = HEIGHT / board.cell_size();
         let mut return_game = Game {
             graphics: graphics,
             random_gen: random_gen,
             grid: vec![vec![Tile::Empty; game_height]; game_width],
-            i2c_3: i2c_3,
-            snake_head_position: (24, 10),
-            snake_body_position: vec![(23, 10), (22, 10)],
-            snake_tail_position: (21, 10),
-            former_snake_tail: (20, 10),
-            apple_position: (1, 10),
-            apple_counter: 0,
+            grid_width: game_width,
+            grid_height: game_height,
+            cell_size: board.cell_size(),
+            board: board,
+            arena: false,
+            camera: Camera::new((WIDTH, HEIGHT), (WIDTH, HEIGHT)),
+            minimap: render::Minimap::new(WIDTH, (game_width, game_height)),
+            minimap_dirty: false,
+            hud_dirty: false,
+            table_top: false,
+            side: RotDirection::R0,
+            players: 1,
+            zones: controls::zones(1, false, (WIDTH, HEIGHT)),
+            rules: DEFAULT_RULES,
+            current_match: Match::new(DEFAULT_RULES, 1),
+            round_started_at: 0,
+            shown_seconds_left: None,
+            touch_screen: touch_screen,
+            sound: sound,
+            touching: false,
+            touch_warning: false,
+            touch_filter: TouchFilter::new(),
+            calibration: settings::load_calibration().unwrap_or_else(Calibration::identity),
+            snakes: Vec::new(),
+            apple_position: (0, 0),
+            dirty_cells: Vec::new(),
+            full_redraw: true,
+            frame_timer: frame_timer::FrameTimer::new(),
+            apple_animation: Animation::new(&render::APPLE_PULSE, Playback::Loop),
+            theme: &theme::CLASSIC,
+            smooth_movement: true,
+            step_started_at: 0,
+            step_duration: 1,
+            drawn_progress: render::PROGRESS_ONE,
+            transition: None,
+            screen_change: ScreenChange::StartGame,
+            screenshots: 0,
+            screenshot_touch: false,
         };
-        return_game.grid[24][10] = Tile::SnakeHead(Direction::right);
+        return_game.place_snakes(2);
+        return_game.apple_animation.start(system_clock::ticks());
         return_game
     }
 
@@ -79,606 +243,1281 @@ impl Game {
      */
 
     pub fn draw_frame(&mut self) {
-        for i in GRID_BLOCK_SIZE - 2..WIDTH - GRID_BLOCK_SIZE {
-            self.graphics.print_square_size_color_at(
-                i,
-                GRID_BLOCK_SIZE - 2,
-                1,
-                lcd::Color {
-                    red: 0,
-                    green: 0,
-                    blue: 0,
-                    alpha: 255,
-                },
-            );
-        }
-        for i in GRID_BLOCK_SIZE - 2..WIDTH - GRID_BLOCK_SIZE {
-            self.graphics.print_square_size_color_at(
-                i,
-                HEIGHT - GRID_BLOCK_SIZE - 1,
-                1,
-                lcd::Color {
-                    red: 0,
-                    green: 0,
-                    blue: 0,
-                    alpha: 255,
-                },
-            );
-        }
-        for i in GRID_BLOCK_SIZE - 2..HEIGHT - GRID_BLOCK_SIZE {
-            self.graphics.print_square_size_color_at(
-                WIDTH - GRID_BLOCK_SIZE,
-                i,
-                1,
-                lcd::Color {
-                    red: 0,
-                    green: 0,
-                    blue: 0,
-                    alpha: 255,
-                },
-            );
-        }
-        for i in GRID_BLOCK_SIZE - 2..HEIGHT - GRID_BLOCK_SIZE {
-            self.graphics.print_square_size_color_at(
-                GRID_BLOCK_SIZE - 2,
-                i,
-                1,
-                lcd::Color {
-                    red: 0,
-                    green: 0,
-                    blue: 0,
-                    alpha: 255,
-                },
-            );
-        }
-    }
-    /**
-     * Draws current game state to screen.
+        let world = self.world_size();
+        let mut viewport = Viewport::new(
+            &mut self.graphics.layer_1,
+            (self.camera.x, self.camera.y),
+            world,
+        );
+        render::draw_frame(&mut viewport, self.cell_size, self.theme);
+    }
+
+    /**
+     * Size of the whole grid in pixels.
+     */
+    fn world_size(&self) -> (usize, usize) {
+        (self.grid_width * self.cell_size, self.grid_height * self.cell_size)
+    }
+    /**
+     * Draws current game state to screen.\n
+     * Only the cells that changed since the last call are redrawn, unless a
+     * full redraw was requested (new game, screen change).
      */
     pub fn draw_game(&mut self) {
-        // draw head (bmp of head)
-        // Bmp
-        let direction = &self.grid[self.snake_head_position.0][self.snake_head_position.1];
-        let mut apple_offset = self.apple_position;
-        let mut rot = self::graphics::RotDirection::R0;
-        match direction {
-            &Tile::SnakeHead(Direction::left) => {
-                rot = self::graphics::RotDirection::R0;
-                apple_offset = (self.apple_position.0 + 1, self.apple_position.1)
-            }
-            &Tile::SnakeHead(Direction::up) => {
-                rot = self::graphics::RotDirection::R90;
-                apple_offset = (self.apple_position.0, self.apple_position.1 + 1)
-            }
-            &Tile::SnakeHead(Direction::right) => {
-                rot = self::graphics::RotDirection::R180;
-                apple_offset = (self.apple_position.0 - 1, self.apple_position.1)
-            }
-            &Tile::SnakeHead(Direction::down) => {
-                rot = self::graphics::RotDirection::R270;
-                apple_offset = (self.apple_position.0, self.apple_position.1 - 1)
-            }
-            _ => {}
-        }
-        // println!("{}, {}, {}", self.snake_head_position.1, self.apple_position.1, apple_offset.1);
-
-        if self.snake_head_position == apple_offset {
-            self.graphics.print_bmp_at_with_rotaion(
-                graphics::SNAKE_MOUTH_OPEN,
-                (self.snake_head_position.0 * GRID_BLOCK_SIZE) as u32,
-                (self.snake_head_position.1 * GRID_BLOCK_SIZE) as u32,
-                rot,
-            );
+        if self.is_changing_screen() {
+            // the old screen is still shown
+            return;
+        }
+        if self.hud_dirty {
+            self.hud_dirty = false;
+            self.draw_hud();
+            // the scores facing the top and the touch warning run through
+            // the minimap, it goes on top of them
+            self.minimap_dirty = true;
+        }
+        if self.minimap_dirty && self.arena {
+            self.minimap_dirty = false;
+            self.draw_minimap();
+        }
+        if !self.full_redraw && self.dirty_cells.is_empty() {
+            return;
+        }
+        self.frame_timer.start();
+        let full_redraw = self.full_redraw;
+        if full_redraw {
+            self.full_redraw = false;
+            self.dirty_cells.clear();
+            self.graphics.layer_1.clear();
+            self.draw_frame();
+
+            let apple = self.apple_position;
+            let mut cells = Vec::new();
+            for snake in self.snakes.iter().filter(|snake| snake.alive) {
+                cells.extend(snake.cells());
+            }
+            for cell in cells {
+                self.draw_cell(cell);
+            }
+            self.draw_cell(apple);
         } else {
-            self.graphics.print_bmp_at_with_rotaion(
-                graphics::SNAKE_MOUTH_CLOSED,
-                (self.snake_head_position.0 * GRID_BLOCK_SIZE) as u32,
-                (self.snake_head_position.1 * GRID_BLOCK_SIZE) as u32,
-                rot,
-            );
-        }
-
-        // self.graphics.print_square_size_color_at(
-        //     self.snake_head_position.0 * GRID_BLOCK_SIZE,
-        //     self.snake_head_position.1 * GRID_BLOCK_SIZE,
-        //     GRID_BLOCK_SIZE - 1,
-        //     lcd::Color {
-        //         red: 0,
-        //         green: 0,
-        //         blue: 0,
-        //         alpha: 255,
-        //     },
-        // );
-
-        // draw body (bmp of body)
-        for i in 0..self.snake_body_position.len() {
-            self.graphics.print_square_size_color_at(
-                self.snake_body_position[i].0 * GRID_BLOCK_SIZE,
-                self.snake_body_position[i].1 * GRID_BLOCK_SIZE,
-                GRID_BLOCK_SIZE - 1,
-                lcd::Color {
-                    red: 100,
-                    green: 100,
-                    blue: 100,
-                    alpha: 255,
-                },
-            );
-        }
-
-        // draw tail (bmp of tail)
-        self.graphics.print_square_size_color_at(
-            self.snake_tail_position.0 * GRID_BLOCK_SIZE,
-            self.snake_tail_position.1 * GRID_BLOCK_SIZE,
-            GRID_BLOCK_SIZE - 1,
-            lcd::Color {
-                red: 255,
-                green: 0,
-                blue: 0,
-                alpha: 255,
-            },
-        );
+            while let Some(cell) = self.dirty_cells.pop() {
+                self.draw_cell(cell);
+            }
+        }
+        if self.is_interpolating() {
+            self.draw_moving_ends();
+        }
+        self.frame_timer.stop(full_redraw);
+    }
 
-        // erase former tail (bmp of tail)
-        if self.former_snake_tail != (0, 0) {
-            self.graphics.print_square_size_color_at(
-                self.former_snake_tail.0 * GRID_BLOCK_SIZE,
-                self.former_snake_tail.1 * GRID_BLOCK_SIZE,
-                GRID_BLOCK_SIZE,
-                lcd::Color {
-                    red: 255,
-                    green: 255,
-                    blue: 255,
-                    alpha: 0,
-                },
-            );
-        }
-
-        // draw apple (bmp of apple)
-        // Bmp
-        self.graphics.print_bmp_at_with_rotaion(
-            graphics::APPLE_BMP,
-            (self.apple_position.0 * GRID_BLOCK_SIZE) as u32,
-            (self.apple_position.1 * GRID_BLOCK_SIZE) as u32,
-            self::graphics::RotDirection::R0,
-        )
-
-        // Quadrat
-        // self.graphics.print_square_size_color_at(
-        //     self.apple_position.0 * GRID_BLOCK_SIZE,
-        //     self.apple_position.1 * GRID_BLOCK_SIZE,
-        //     GRID_BLOCK_SIZE - 1,
-        //     lcd::Color {
-        //         red: 255,
-        //         green: 255,
-        //         blue: 0,
-        //         alpha: 255,
-        //     },
-        // );
-    }
-
-    /**
-     * Moves position of the snake in chosen direction.
-     */
-    fn move_up(&mut self) {
-        let x = self.snake_head_position.0;
-        let y = self.snake_head_position.1;
-
-        self.grid[x][y - 1] = Tile::SnakeHead(Direction::up);
-        self.grid[x][y] = Tile::Empty;
-        self.former_snake_tail = self.snake_tail_position;
-        self.snake_tail_position = self.snake_body_position[self.snake_body_position.len() - 1];
-        for z in (0..self.snake_body_position.len() - 1).rev() {
-            self.snake_body_position[z + 1] = self.snake_body_position[z];
+    /**
+     * Tells the game that a tick just happened and how long it takes until
+     * the next one, so smooth movement can interpolate in between. The
+     * music follows the speed of the game.
+     */
+    pub fn start_step(&mut self, duration: usize) {
+        self.step_started_at = system_clock::ticks();
+        self.step_duration = duration.max(1);
+        if let Some(ref mut sound) = self.sound {
+            sound.set_tempo((MUSIC_TICK * 100 / self.step_duration) as u32);
         }
-        self.snake_body_position[0] = self.snake_head_position;
-        self.snake_head_position.1 = y - 1;
+    }
 
-        return;
+    /**
+     * How far the snake has moved from its last cells towards the current ones.
+     */
+    fn step_progress(&self) -> u32 {
+        let elapsed = system_clock::ticks().wrapping_sub(self.step_started_at);
+        let progress = (elapsed << render::PROGRESS_BITS) / self.step_duration;
+        (progress as u32).min(render::PROGRESS_ONE)
     }
 
     /**
-     * Moves position of the snake in chosen direction.
+     * Head and tail are drawn in between cells instead of with their cell.
      */
-    fn move_down(&mut self) {
-        let x = self.snake_head_position.0;
-        let y = self.snake_head_position.1;
+    fn is_interpolating(&self) -> bool {
+        self.smooth_movement && self.drawn_progress < render::PROGRESS_ONE
+    }
 
-        self.grid[x][y + 1] = Tile::SnakeHead(Direction::down);
-        self.grid[x][y] = Tile::Empty;
-        self.former_snake_tail = self.snake_tail_position;
-        self.snake_tail_position = self.snake_body_position[self.snake_body_position.len() - 1];
-        for z in (0..self.snake_body_position.len() - 1).rev() {
-            self.snake_body_position[z + 1] = self.snake_body_position[z];
+    /**
+     * Draws head and tail of every snake at their position in between two
+     * cells. The cells they cover have been redrawn without them.
+     */
+    fn draw_moving_ends(&mut self) {
+        for i in 0..self.snakes.len() {
+            if self.snakes[i].alive {
+                self.draw_moving_snake_ends(i);
+            }
         }
-        self.snake_body_position[0] = self.snake_head_position;
-        self.snake_head_position.1 = y + 1;
+    }
 
-        return;
+    fn draw_moving_snake_ends(&mut self, player: usize) {
+        let progress = self.drawn_progress;
+        let (head, neck, tail, former_tail) = {
+            let snake = &self.snakes[player];
+            (snake.head, snake.body[0], snake.tail, snake.former_tail)
+        };
+        if former_tail != (0, 0) {
+            let (x, y) = render::interpolate(former_tail, tail, progress, self.cell_size);
+            let content = match self.grid[tail.0][tail.1] {
+                Tile::SnakeTail(exit) => tail_content(player, exit),
+                _ => tail_content(player, Direction::right),
+            };
+            self.draw_content_at(x, y, content);
+        }
+        let (x, y) = render::interpolate(neck, head, progress, self.cell_size);
+        let content = self.head_content(player);
+        self.draw_content_at(x, y, content);
     }
 
     /**
-     * Moves position of the snake in chosen direction.
+     * Draws something at the pixel position x,y of the world.
      */
-    fn move_right(&mut self) {
-        let x = self.snake_head_position.0;
-        let y = self.snake_head_position.1;
+    fn draw_content_at(&mut self, x: usize, y: usize, content: render::CellContent) {
+        let world = self.world_size();
+        let mut viewport = Viewport::new(
+            &mut self.graphics.layer_1,
+            (self.camera.x, self.camera.y),
+            world,
+        );
+        render::draw_content_at(&mut viewport, x, y, self.cell_size, content, self.theme);
+    }
 
-        self.grid[x + 1][y] = Tile::SnakeHead(Direction::right);
-        self.grid[x][y] = Tile::Empty;
-        self.former_snake_tail = self.snake_tail_position;
-        self.snake_tail_position = self.snake_body_position[self.snake_body_position.len() - 1];
-        for z in (0..self.snake_body_position.len() - 1).rev() {
-            self.snake_body_position[z + 1] = self.snake_body_position[z];
+    /**
+     * Draws the overview of the arena in the top right corner of layer 2.
+     */
+    fn draw_minimap(&mut self) {
+        let cells: Vec<(usize, Vec<(usize, usize)>)> = self.snakes
+            .iter()
+            .enumerate()
+            .filter(|&(_, snake)| snake.alive)
+            .map(|(player, snake)| (player, snake.cells()))
+            .collect();
+        let snakes: Vec<(usize, &[(usize, usize)])> = cells
+            .iter()
+            .map(|&(player, ref cells)| (player, &cells[..]))
+            .collect();
+        let view = (
+            self.camera.x / self.cell_size,
+            self.camera.y / self.cell_size,
+            WIDTH / self.cell_size,
+            HEIGHT / self.cell_size,
+        );
+        render::draw_minimap(
+            &mut self.graphics.layer_2,
+            &self.minimap,
+            self.theme,
+            &snakes,
+            &[self.apple_position],
+            view,
+        );
+    }
+
+    /**
+     * Draws the score of every player at the bottom of their control zone on
+     * layer 2, facing them, and the time left with a time limit.
+     */
+    fn draw_hud(&mut self) {
+        let zones = self.zones.clone();
+        let time = match self.shown_seconds_left {
+            Some(seconds) => format!("  {}:{:02}", seconds / 60, seconds % 60),
+            None => String::new(),
+        };
+        for zone in zones {
+            let score = self.snakes[zone.player].score;
+            let (text, color) = if self.snakes.len() == 1 {
+                (format!("score {}{}", score, time), self.theme.ui.foreground)
+            } else {
+                let text = format!("player {}: {}{}", zone.player + 1, score, time);
+                (text, self.theme.player_color(zone.player))
+            };
+            let area = zone.view_area((WIDTH, HEIGHT));
+            let mut layer = Rotated::new(&mut self.graphics.layer_2, zone.side);
+            render::draw_hud(&mut layer, area, &text, color);
         }
-        self.snake_body_position[0] = self.snake_head_position;
-        self.snake_head_position.0 = x + 1;
+    }
 
-        return;
+    /**
+     * Splits the screen into control zones for the current snakes.
+     */
+    fn update_zones(&mut self) {
+        self.zones = controls::zones(self.snakes.len(), self.table_top, (WIDTH, HEIGHT));
     }
 
     /**
-     * Moves position of the snake in chosen direction.
+     * The snake the camera follows in arena mode, the first one alive.
      */
-    fn move_left(&mut self) {
-        let x = self.snake_head_position.0;
-        let y = self.snake_head_position.1;
+    fn followed_snake(&self) -> usize {
+        self.snakes
+            .iter()
+            .position(|snake| snake.alive)
+            .unwrap_or(0)
+    }
 
-        self.grid[x - 1][y] = Tile::SnakeHead(Direction::left);
-        self.grid[x][y] = Tile::Empty;
-        self.former_snake_tail = self.snake_tail_position;
-        self.snake_tail_position = self.snake_body_position[self.snake_body_position.len() - 1];
-        for z in (0..self.snake_body_position.len() - 1).rev() {
-            self.snake_body_position[z + 1] = self.snake_body_position[z];
+    /**
+     * Pixel position of the center of the head, in between two cells while
+     * the snake moves smoothly.
+     */
+    fn head_pixel_position(&self) -> (usize, usize) {
+        let progress = if self.smooth_movement {
+            self.step_progress()
+        } else {
+            render::PROGRESS_ONE
+        };
+        let snake = &self.snakes[self.followed_snake()];
+        let (x, y) = render::interpolate(snake.body[0], snake.head, progress, self.cell_size);
+        (x + self.cell_size / 2, y + self.cell_size / 2)
+    }
+
+    /**
+     * Advances the animations to the current time and marks the cells they
+     * changed. Call as often as possible, independent of the game ticks.
+     */
+    pub fn animate(&mut self) {
+        self.update_sound();
+        let now = system_clock::ticks();
+        for i in 0..self.snakes.len() {
+            if self.snakes[i].head_animation.tick(now) {
+                let head = self.snakes[i].head;
+                self.mark_dirty(head);
+            }
+        }
+        if self.apple_animation.tick(now) {
+            let apple = self.apple_position;
+            self.mark_dirty(apple);
         }
-        self.snake_body_position[0] = self.snake_head_position;
-        self.snake_head_position.0 = x - 1;
+        if self.smooth_movement {
+            // only redraw when head and tail move by at least a pixel
+            let progress = self.step_progress();
+            let cell_size = self.cell_size;
+            let pixels = |progress| (progress as usize * cell_size) >> render::PROGRESS_BITS;
+            if pixels(progress) != pixels(self.drawn_progress) {
+                self.drawn_progress = progress;
+                for i in 0..self.snakes.len() {
+                    if self.snakes[i].alive {
+                        self.mark_move_dirty(i);
+                    }
+                }
+            }
+        }
+        let seconds_left = self.seconds_left(now);
+        if seconds_left != self.shown_seconds_left {
+            self.shown_seconds_left = seconds_left;
+            self.hud_dirty = true;
+        }
+        if self.arena {
+            let head = self.head_pixel_position();
+            let old_position = (self.camera.x, self.camera.y);
+            if self.camera.follow(head) {
+                self.scroll_view(old_position);
+            }
+        }
+        self.update_transition(now);
+    }
 
-        return;
+    /**
+     * Moves the picture on layer 1 along with the camera, which was at
+     * `old_position`, and redraws only the strips that came into view. A jump
+     * of a screen or more, when the head wrapped around the world, redraws
+     * everything.
+     */
+    fn scroll_view(&mut self, old_position: (usize, usize)) {
+        if self.full_redraw {
+            return;
+        }
+        let (x, y) = (self.camera.x, self.camera.y);
+        let dx = x as isize - old_position.0 as isize;
+        let dy = y as isize - old_position.1 as isize;
+        if dx.abs() as usize >= WIDTH || dy.abs() as usize >= HEIGHT {
+            self.full_redraw = true;
+            return;
+        }
+        self.graphics.scroll_layer_1(-dx, -dy);
+        // the strips in world pixels as (x, y, width, height)
+        let (shift_x, shift_y) = (dx.abs() as usize, dy.abs() as usize);
+        let column = if dx > 0 { x + WIDTH - shift_x } else { x };
+        let row = if dy > 0 { y + HEIGHT - shift_y } else { y };
+        let strips = [(column, y, shift_x, HEIGHT), (x, row, WIDTH, shift_y)];
+        for &(left, top, width, height) in strips.iter().filter(|strip| strip.2 * strip.3 > 0) {
+            self.clear_world_area(left, top, width, height);
+            let cell_size = self.cell_size;
+            for cell_x in left / cell_size..(left + width - 1) / cell_size + 1 {
+                for cell_y in top / cell_size..(top + height - 1) / cell_size + 1 {
+                    self.mark_dirty((cell_x, cell_y));
+                }
+            }
+        }
+        // the frame runs through the cells next to the edges, which are
+        // never redrawn
+        self.draw_frame();
     }
 
     /**
-     * Calls the correct function to turn to move the snake straight forward
+     * Erases an area of layer 1 given in world pixels.
      */
+    fn clear_world_area(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        let world = self.world_size();
+        let mut viewport = Viewport::new(
+            &mut self.graphics.layer_1,
+            (self.camera.x, self.camera.y),
+            world,
+        );
+        viewport.fill_rect(x, y, width, height, Color::transparent());
+    }
 
-    pub fn move_straight(&mut self) {
-        if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            == Tile::SnakeHead(Direction::up)
-        {
-            self.move_up();
-        } else if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            == Tile::SnakeHead(Direction::down)
-        {
-            self.move_down();
-        } else if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            == Tile::SnakeHead(Direction::left)
-        {
-            self.move_left();
-        } else if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            == Tile::SnakeHead(Direction::right)
-        {
-            self.move_right();
+    /**
+     * Seconds left in the round if the goal has a time limit.
+     */
+    fn seconds_left(&self, now: usize) -> Option<usize> {
+        match self.current_match.rules.goal {
+            Goal::TimeLimit(duration) => {
+                let elapsed = now.wrapping_sub(self.round_started_at);
+                Some((duration.saturating_sub(elapsed) + 999) / 1000)
+            }
+            _ => None,
         }
     }
 
     /**
-     * Calls the correct function to turn the snake to the right
+     * Starts a transition to another screen. The game keeps running, the
+     * change itself happens once the old screen is hidden.
      */
+    fn start_transition(&mut self, effect: Effect, overlay_only: bool, change: ScreenChange) {
+        let now = system_clock::ticks();
+        self.transition = Some(Transition::new(effect, overlay_only, TRANSITION_DURATION, now));
+        self.screen_change = change;
+    }
 
-    pub fn turn_right(&mut self) {
-        if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            == Tile::SnakeHead(Direction::up)
-        {
-            self.move_right();
-        } else if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            == Tile::SnakeHead(Direction::down)
-        {
-            self.move_left();
-        } else if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            == Tile::SnakeHead(Direction::left)
-        {
-            self.move_up();
-        } else if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            == Tile::SnakeHead(Direction::right)
-        {
-            self.move_down();
+    /**
+     * True while a transition still hides the old screen. The snake must not
+     * move until the new screen is set up.
+     */
+    pub fn is_changing_screen(&self) -> bool {
+        match self.transition {
+            Some(ref transition) => transition.phase() == Phase::Out,
+            None => false,
         }
     }
 
+    fn update_transition(&mut self, now: usize) {
+        let (effect, overlay_only, coverage, phase_changed, phase) = match self.transition {
+            Some(ref mut transition) => {
+                let phase_changed = transition.advance(now);
+                (
+                    transition.effect,
+                    transition.overlay_only,
+                    transition.coverage(now),
+                    phase_changed,
+                    transition.phase(),
+                )
+            }
+            None => return,
+        };
+        if phase_changed && phase == Phase::In {
+            self.change_screen();
+        }
+        if phase == Phase::Done {
+            self.transition = None;
+            self.graphics.end_transition();
+        } else {
+            self.graphics.apply_transition(effect, coverage, overlay_only);
+        }
+    }
+
+    fn change_screen(&mut self) {
+        match self.screen_change {
+            ScreenChange::StartGame => {
+                self.graphics.layer_1.clear();
+                self.graphics.layer_2.clear();
+                self.full_redraw = true;
+            }
+            ScreenChange::NewGame => {
+                self.graphics.layer_1.clear();
+                self.graphics.layer_2.clear();
+                self.current_match = Match::new(self.rules, self.players);
+                self.reset();
+            }
+            ScreenChange::NextRound => {
+                self.graphics.layer_1.clear();
+                self.graphics.layer_2.clear();
+                if self.current_match.wins.len() != self.players {
+                    // the number of players changed, that is another match
+                    self.current_match = Match::new(self.rules, self.players);
+                }
+                self.reset();
+            }
+            ScreenChange::Resume => self.graphics.layer_2.clear(),
+        }
+        self.minimap_dirty = true;
+        self.hud_dirty = true;
+    }
+
     /**
-     * Calls the correct function to turn the snake to the left
+     * Marks a cell to be redrawn by the next `draw_game`.
      */
+    fn mark_dirty(&mut self, cell: (usize, usize)) {
+        if !self.dirty_cells.contains(&cell) {
+            self.dirty_cells.push(cell);
+        }
+    }
 
-    pub fn turn_left(&mut self) {
-        if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            == Tile::SnakeHead(Direction::up)
-        {
-            self.move_left();
-        } else if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            == Tile::SnakeHead(Direction::down)
-        {
-            self.move_right();
-        } else if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            == Tile::SnakeHead(Direction::left)
-        {
-            self.move_down();
-        } else if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            == Tile::SnakeHead(Direction::right)
+    /**
+     * Marks the cells that changed by moving a snake one step.
+     */
+    fn mark_move_dirty(&mut self, player: usize) {
+        let (head, neck, tail, former_tail) = {
+            let snake = &self.snakes[player];
+            (snake.head, snake.body[0], snake.tail, snake.former_tail)
+        };
+        self.mark_dirty(head);
+        self.mark_dirty(neck);
+        self.mark_dirty(tail);
+        self.mark_dirty(former_tail);
+    }
+
+    /**
+     * Cells on the edge of the grid are covered by the frame and never hold a tile.
+     */
+    fn is_inside_frame(&self, cell: (usize, usize)) -> bool {
+        cell.0 > 0 && cell.1 > 0 && cell.0 < self.grid_width - 1 && cell.1 < self.grid_height - 1
+    }
+
+    /**
+     * Draws whatever currently occupies a cell (head, body, tail or apple).
+     */
+    fn draw_cell(&mut self, cell: (usize, usize)) {
+        if !self.is_inside_frame(cell) {
+            return;
+        }
+        let cell_size = self.cell_size;
+        let (x, y) = (cell.0 * cell_size, cell.1 * cell_size);
+        if !self.camera.is_visible(x, y, cell_size, cell_size) {
+            return;
+        }
+        let content = self.cell_content(cell);
+        let world = self.world_size();
+        let mut viewport = Viewport::new(
+            &mut self.graphics.layer_1,
+            (self.camera.x, self.camera.y),
+            world,
+        );
+        render::draw_cell(&mut viewport, cell, cell_size, content, self.theme);
+    }
+
+    fn cell_content(&self, cell: (usize, usize)) -> render::CellContent {
+        let interpolating = self.is_interpolating();
+        for (player, snake) in self.snakes.iter().enumerate() {
+            if !snake.alive {
+                continue;
+            }
+            if cell == snake.head {
+                return if interpolating {
+                    // drawn by draw_moving_ends
+                    render::CellContent::Empty
+                } else {
+                    self.head_content(player)
+                };
+            } else if snake.body.contains(&cell) {
+                return match self.grid[cell.0][cell.1] {
+                    Tile::SnakeBody(entry, exit) => body_content(player, entry, exit),
+                    _ => body_content(player, Direction::right, Direction::right),
+                };
+            } else if cell == snake.tail {
+                return match (&self.grid[cell.0][cell.1], &snake.former_tail_tile) {
+                    // the tail is drawn by draw_moving_ends and slides over the
+                    // body segment this cell was, entered from the former tail
+                    (&Tile::SnakeTail(exit), &Tile::SnakeTail(entry))
+                        if interpolating && snake.former_tail != (0, 0) =>
+                    {
+                        body_content(player, entry, exit)
+                    }
+                    (&Tile::SnakeTail(exit), _) => tail_content(player, exit),
+                    _ => tail_content(player, Direction::right),
+                };
+            }
+        }
+        if cell == self.apple_position {
+            render::CellContent::Apple {
+                sprite: self.apple_animation.value().unwrap_or(&assets::APPLE),
+            }
+        } else {
+            render::CellContent::Empty
+        }
+    }
+
+    /**
+     * The head of a snake, with open mouth if the apple is right in front of
+     * it.
+     */
+    fn head_content(&self, player: usize) -> render::CellContent {
+        let snake = &self.snakes[player];
+        let apple = self.apple_position;
+        let (rot, apple_offset) = match snake.direction {
+            Direction::left => (RotDirection::R0, (apple.0 + 1, apple.1)),
+            Direction::up => (RotDirection::R90, (apple.0, apple.1 + 1)),
+            Direction::right => (RotDirection::R180, (apple.0 - 1, apple.1)),
+            Direction::down => (RotDirection::R270, (apple.0, apple.1 - 1)),
+        };
+        let chomping = snake.head_animation.value();
+        render::CellContent::Head {
+            player: player,
+            rot: rot,
+            mouth_open: chomping.unwrap_or(snake.head == apple_offset),
+        }
+    }
+
+    /**
+     * Moves the head of a snake one cell in `direction` to `new_head` and lets
+     * the rest of it follow. The cell the head left becomes a body segment
+     * that remembers in which direction it was entered and left.
+     */
+    fn step(&mut self, player: usize, direction: Direction, new_head: (usize, usize)) {
         {
-            self.move_up();
+            let snake = &mut self.snakes[player];
+            let grid = &mut self.grid;
+            let old_head = snake.head;
+            let old_direction = snake.direction;
+
+            // the tail moves onto the last body segment
+            snake.former_tail = snake.tail;
+            snake.former_tail_tile = grid[snake.former_tail.0][snake.former_tail.1].clone();
+            grid[snake.former_tail.0][snake.former_tail.1] = Tile::Empty;
+            snake.tail = snake.body[snake.body.len() - 1];
+            let tail = snake.tail;
+            if let Tile::SnakeBody(_, exit) = grid[tail.0][tail.1] {
+                grid[tail.0][tail.1] = Tile::SnakeTail(exit);
+            }
+            for z in (0..snake.body.len() - 1).rev() {
+                snake.body[z + 1] = snake.body[z];
+            }
+
+            grid[old_head.0][old_head.1] = Tile::SnakeBody(old_direction, direction);
+            snake.body[0] = old_head;
+            // a head that runs into another snake keeps its tile, the snake
+            // dies in `check_selfbite`
+            if let Tile::Empty = grid[new_head.0][new_head.1] {
+                grid[new_head.0][new_head.1] = Tile::SnakeHead(direction);
+            }
+            snake.head = new_head;
+            snake.direction = direction;
+        }
+        self.mark_move_dirty(player);
+        self.minimap_dirty = true;
+        if self.smooth_movement {
+            // head and tail start over at their previous cells
+            self.drawn_progress = 0;
         }
     }
 
     /**
-     * Sets the direction chosen by the user
+     * Moves a snake one cell in `direction`.
+     */
+    fn move_towards(&mut self, player: usize, direction: Direction) {
+        let (x, y) = self.snakes[player].head;
+        let new_head = match direction {
+            Direction::up => (x, y - 1),
+            Direction::down => (x, y + 1),
+            Direction::left => (x - 1, y),
+            Direction::right => (x + 1, y),
+        };
+        self.step(player, direction, new_head);
+    }
+
+    /**
+     * Moves a snake straight forward.
+     */
+    pub fn move_straight(&mut self, player: usize) {
+        let direction = self.snakes[player].direction;
+        self.move_towards(player, direction);
+    }
+
+    /**
+     * Moves a snake to the right, seen from its head.
+     */
+    pub fn turn_right(&mut self, player: usize) {
+        let direction = self.snakes[player].direction.turned_right();
+        self.move_towards(player, direction);
+        self.play(mixer::Effect::Turn);
+    }
+
+    /**
+     * Moves a snake to the left, seen from its head.
+     */
+    pub fn turn_left(&mut self, player: usize) {
+        let direction = self.snakes[player].direction.turned_left();
+        self.move_towards(player, direction);
+        self.play(mixer::Effect::Turn);
+    }
+
+    /**
+     * Moves every snake in the direction chosen by its player. Every touch
+     * point counts on its own, in the control zone it falls into.
      */
     pub fn move_snake(&mut self) {
-        let touches = self.get_touches();
-        if touches.len() == 1 {
-            let mut touch = touches[0];
-            let mut x = touch.0;
-            let mut y = touch.1;
-
-            if x < 100 {
-                self.turn_left();
-            } else if x > 380 {
-                self.turn_right();
-            } else if x > 100 && x < 380 {
-                self.pause_game();
+        let mut touches = self.get_touches();
+        // touching with two fingers takes a screenshot, once per touch; with
+        // more than one snake two fingers are just two players steering
+        let screenshot_touch = self.snakes.len() == 1 && touches.len() == 2;
+        // a short tap between two ticks steers as well
+        touches.extend(self.touch_filter.take_taps());
+        if screenshot_touch && !self.screenshot_touch {
+            self.take_screenshot();
+        }
+        self.screenshot_touch = screenshot_touch;
+
+        let mut controls = [None; MAX_PLAYERS];
+        let mut pause = None;
+        if !screenshot_touch {
+            for &(x, y) in &touches {
+                let point = (x as usize, y as usize);
+                for zone in &self.zones {
+                    match zone.control(point, (WIDTH, HEIGHT)) {
+                        Some(Control::Pause) => pause = Some(zone.side),
+                        // the first finger in a zone steers
+                        Some(control) => if controls[zone.player].is_none() {
+                            controls[zone.player] = Some(control);
+                            self.side = zone.side;
+                        },
+                        None => {}
+                    }
+                }
+            }
+        }
+        if let Some(side) = pause {
+            // the menu faces whoever paused
+            self.side = side;
+            self.pause_game();
+            return;
+        }
+        for player in 0..self.snakes.len() {
+            if !self.snakes[player].alive {
+                continue;
+            }
+            match controls[player] {
+                Some(Control::TurnLeft) => self.turn_left(player),
+                Some(Control::TurnRight) => self.turn_right(player),
+                _ => self.move_straight(player),
             }
-        } else {
-            self.move_straight();
         }
     }
 
     /**
-     * checks if snake bites
+     * Lets a snake that reached the apple eat it.
      */
     pub fn snake_bite(&mut self) {
-        if self.snake_head_position == self.apple_position {
-            self.snake_body_position.push(self.snake_tail_position);
-            self.snake_tail_position = self.former_snake_tail;
-            self.former_snake_tail = (0, 0); // has to be improved
-            let x = self.random_gen
-                .random_range(1, WIDTH as u32 / GRID_BLOCK_SIZE as u32 - 1);
-            let y = self.random_gen
-                .random_range(1, HEIGHT as u32 / GRID_BLOCK_SIZE as u32 - 1);
-            self.apple_position = (x as usize, y as usize);
-            self.apple_counter = self.apple_counter + 1;
+        let apple = self.apple_position;
+        let eater = self.snakes
+            .iter()
+            .position(|snake| snake.alive && snake.head == apple);
+        if let Some(player) = eater {
+            self.eat_apple(player);
         }
     }
 
     /**
-     * returns touches array
+     * The snake grows by one segment and the apple moves somewhere else.
+     */
+    fn eat_apple(&mut self, player: usize) {
+        {
+            let snake = &mut self.snakes[player];
+            let grid = &mut self.grid;
+            // the tail becomes a body segment again, entered from the former tail
+            let tail = snake.tail;
+            if let (&Tile::SnakeTail(entry), &Tile::SnakeTail(exit)) =
+                (&snake.former_tail_tile, &grid[tail.0][tail.1])
+            {
+                grid[tail.0][tail.1] = Tile::SnakeBody(entry, exit);
+            }
+            let former_tail = snake.former_tail;
+            grid[former_tail.0][former_tail.1] = snake.former_tail_tile.clone();
+
+            snake.body.push(snake.tail);
+            snake.tail = snake.former_tail;
+            snake.former_tail = (0, 0); // has to be improved
+            snake.score += 1;
+            snake.head_animation.start(system_clock::ticks());
+        }
+        let x = self.random_gen
+            .random_range(1, self.grid_width as u32 - 1);
+        let y = self.random_gen
+            .random_range(1, self.grid_height as u32 - 1);
+        self.apple_position = (x as usize, y as usize);
+        self.hud_dirty = true;
+        self.play(mixer::Effect::Eat);
+
+        let tail = self.snakes[player].tail;
+        let apple = self.apple_position;
+        self.mark_dirty(tail);
+        self.mark_dirty(apple);
+    }
+
+    /**
+     * returns the points of the fingers that are pressed, see `TouchFilter`
      */
     pub fn get_touches(&mut self) -> Vec<(u16, u16)> {
-        // &touch::touches(&mut self.i2c_3).unwrap()
-        let mut touches = Vec::new();
-        for touch in &touch::touches(&mut self.i2c_3).unwrap() {
-            // .print_point_at(touch.x as usize, touch.y as usize);
-            touches.push((touch.x, touch.y));
+        self.sample_touches();
+        self.touch_filter.touches()
+    }
+
+    /**
+     * Reads the touch screen and passes the calibrated points to the touch
+     * filter. It should be called every frame, so that the filter sees how
+     * long fingers stay. A failed read is left out, the filter keeps the
+     * fingers it knows.
+     */
+    pub fn sample_touches(&mut self) {
+        let points = match self.read_touches() {
+            Some(points) => points,
+            None => return,
+        };
+        let calibration = self.calibration;
+        let points: Vec<(u16, u16)> = points
+            .iter()
+            .map(|&point| calibration.apply(point, (WIDTH, HEIGHT)))
+            .collect();
+        self.touch_filter.update(&points, system_clock::ticks());
+    }
+
+    /**
+     * The points touched, `None` if the touch screen could not be read.
+     *
+     * The touch screen is only read after the controller signalled a touch
+     * and while fingers are down, so that lifting them is noticed. While
+     * reading keeps failing, a warning is shown on top of the screen.
+     */
+    fn read_touches(&mut self) -> Option<Vec<(u16, u16)>> {
+        if !scheduler::take_touch_signal() && !self.touching {
+            return Some(Vec::new());
+        }
+        let touches = self.touch_screen.touches().ok();
+        self.touching = touches.as_ref().map_or(true, |touches| !touches.is_empty());
+        let failing = self.touch_screen.is_failing();
+        if failing != self.touch_warning {
+            self.touch_warning = failing;
+            render::draw_touch_warning(&mut self.graphics.layer_2, failing, self.theme.ui);
+            // the warning may have covered the scores and the minimap,
+            // redrawing the scores redraws both
+            self.hud_dirty = true;
         }
         touches
     }
 
     /**
-     * returns touches array
+     * Sleeps for `ms` or until the touch controller signals a touch, the
+     * sound keeps playing.
+     */
+    fn wait_for_touch(&mut self, ms: usize) {
+        let deadline = system_clock::ticks() + ms;
+        while system_clock::ticks() < deadline && !scheduler::touch_signalled() {
+            self.update_sound();
+            scheduler::sleep();
+        }
+    }
+
+    /**
+     * Lets the snakes that reached the frame come in on the opposite side.
      */
     pub fn check_grid_edge(&mut self) {
-        if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            != Tile::SnakeHead(Direction::left)
-            && self.snake_head_position.0 == WIDTH / GRID_BLOCK_SIZE - 1
-        {
-            self.grid[self.snake_head_position.0][self.snake_head_position.1] = Tile::Empty;
-            self.snake_head_position.0 = 1;
-            self.grid[self.snake_head_position.0][self.snake_head_position.1] =
-                Tile::SnakeHead(Direction::right);
-        } else if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            != Tile::SnakeHead(Direction::right)
-            && self.snake_head_position.0 == 0
-        {
-            self.grid[self.snake_head_position.0][self.snake_head_position.1] = Tile::Empty;
-            self.snake_head_position.0 = WIDTH / GRID_BLOCK_SIZE - 2;
-            self.grid[self.snake_head_position.0][self.snake_head_position.1] =
-                Tile::SnakeHead(Direction::left);
-        } else if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            != Tile::SnakeHead(Direction::down) && self.snake_head_position.1 == 0
-        {
-            self.grid[self.snake_head_position.0][self.snake_head_position.1] = Tile::Empty;
-            self.snake_head_position.1 = HEIGHT / GRID_BLOCK_SIZE - 2;
-
-            self.grid[self.snake_head_position.0][self.snake_head_position.1] =
-                Tile::SnakeHead(Direction::up);
-        } else if self.grid[self.snake_head_position.0][self.snake_head_position.1]
-            != Tile::SnakeHead(Direction::up)
-            && self.snake_head_position.1 == HEIGHT / GRID_BLOCK_SIZE - 1
-        {
-            self.grid[self.snake_head_position.0][self.snake_head_position.1] = Tile::Empty;
-            self.snake_head_position.1 = 1;
-            self.grid[self.snake_head_position.0][self.snake_head_position.1] =
-                Tile::SnakeHead(Direction::down);
+        for player in 0..self.snakes.len() {
+            if self.snakes[player].alive {
+                self.wrap_around(player);
+            }
         }
     }
+
+    fn wrap_around(&mut self, player: usize) {
+        let ((x, y), direction) = (self.snakes[player].head, self.snakes[player].direction);
+        let wrapped = match direction {
+            Direction::right if x == self.grid_width - 1 => (1, y),
+            Direction::left if x == 0 => (self.grid_width - 2, y),
+            Direction::up if y == 0 => (x, self.grid_height - 2),
+            Direction::down if y == self.grid_height - 1 => (x, 1),
+            _ => return,
+        };
+        self.grid[x][y] = Tile::Empty;
+        if let Tile::Empty = self.grid[wrapped.0][wrapped.1] {
+            self.grid[wrapped.0][wrapped.1] = Tile::SnakeHead(direction);
+        }
+        self.snakes[player].head = wrapped;
+        self.mark_dirty(wrapped);
+    }
+
     /**
      * Set backround color
      */
     pub fn set_backround_color(&mut self) {
-        for x in 5..WIDTH - 5 {
-            for y in 5..HEIGHT - 5 {
-                self.graphics.print_square_size_color_at(
-                    x,
-                    y,
-                    1,
-                    lcd::Color {
-                        red: 209,
-                        green: 115,
-                        blue: 28,
-                        alpha: 255,
-                    },
-                );
+        let color = self.theme.background;
+        self.graphics
+            .layer_1
+            .fill_rect(5, 5, WIDTH - 10, HEIGHT - 10, color);
+    }
+
+    /**
+     * Switches to another theme and redraws the game with it.
+     */
+    pub fn set_theme(&mut self, theme: &'static Theme) {
+        self.theme = theme;
+        self.graphics.set_background(theme.background);
+        self.full_redraw = true;
+        self.draw_game();
+    }
+
+    fn pause_game(&mut self) {
+        // the menu covers the minimap and the score, both are drawn again
+        // when the game resumes
+        self.graphics.layer_2.clear();
+        self.hud_dirty = false;
+        let options = render::Options {
+            smooth_movement: self.smooth_movement,
+            arena: self.arena,
+            table_top: self.table_top,
+            players: self.players,
+            rules: self.rules,
+            board: self.board,
+            theme: self.theme,
+        };
+        let mut menu = render::pause_menu(&options, (WIDTH, HEIGHT));
+        menu.draw(&mut Rotated::new(&mut self.graphics.layer_2, self.side));
+        self.print_scores("     ");
+        // the clock of the round stops while paused
+        let paused_at = system_clock::ticks();
+        let action = self.run_menu(&mut menu);
+        self.round_started_at += system_clock::ticks() - paused_at;
+        match action {
+            MenuAction::NewGame => {
+                self.start_transition(Effect::Slide, false, ScreenChange::NewGame)
             }
+            _ => self.start_transition(Effect::Fade, true, ScreenChange::Resume),
         }
     }
 
-    fn pause_game(&mut self) {
-        self.graphics.print_pause_screen();
-        println!("     score: {}", self.apple_counter);
-        let mut pause = true;
-        let mut new_game = false;
-        loop {
-            let touches = self.get_touches();
+    /**
+     * Ends the round when the rules say so, see `Rules::round_outcome`.
+     */
+    pub fn check_round_end(&mut self) {
+        let scores: Vec<usize> = self.snakes.iter().map(|snake| snake.score).collect();
+        let alive: Vec<bool> = self.snakes.iter().map(|snake| snake.alive).collect();
+        let elapsed = system_clock::ticks().wrapping_sub(self.round_started_at);
+        let outcome = self.current_match
+            .rules
+            .round_outcome(&scores, &alive, elapsed);
+        if let Some(outcome) = outcome {
+            self.end_round(outcome);
+        }
+    }
 
-            if touches.len() == 1 {
-                for touch in touches {
-                    let mut x = touch.0;
-                    let mut y = touch.1;
+    /**
+     * Shows the results of the round, or of the match after its last round,
+     * and starts the next one when the button is clicked. A single player
+     * playing one round gets the game over screen.
+     */
+    fn end_round(&mut self, outcome: Outcome) {
+        let alarm = self.theme.alarm;
+        {
+            let sound = &mut self.sound;
+            self.graphics.background_blink(alarm, |ms| sound::wait(sound, ms));
+        }
+        self.current_match.finish_round(outcome);
+        let match_over = self.current_match.is_over();
 
-                    if (x > 100 + 8 + 90 && x < 100 + 8 + 90 + 100)
-                        && (y > 6 + 139 && y < 6 + 139 + 30)
-                    {
-                        pause = false;
-                        break;
-                    }
-                    if (x > 100 + 8 + 78 && x < 100 + 8 + 90 + 120)
-                        && (y > 6 + 192 && y < 6 + 192 + 30)
-                    {
-                        new_game = true;
-                        break;
-                    }
-                }
-            }
-            if !pause {
-                self.graphics.layer_2.clear();
-                break;
-            }
-            if new_game {
-                self.graphics.layer_2.clear();
-                self.graphics.layer_1.clear();
-                break;
+        self.graphics.layer_2.clear();
+        self.hud_dirty = false;
+        let mut menu = if self.snakes.len() == 1 && self.current_match.rules.rounds == 1 {
+            render::restart_menu(self.theme, (WIDTH, HEIGHT))
+        } else {
+            self.scoreboard(outcome, match_over)
+        };
+        menu.draw(&mut Rotated::new(&mut self.graphics.layer_2, self.side));
+        self.print_scores("   ");
+        let change = match self.run_menu(&mut menu) {
+            MenuAction::NextRound => ScreenChange::NextRound,
+            _ => ScreenChange::NewGame,
+        };
+        self.start_transition(Effect::Wipe, false, change);
+    }
+
+    /**
+     * The results after a round: the winner of the round or, once the match
+     * is over, of the match and the apples and rounds won of every player.
+     */
+    fn scoreboard(&self, outcome: Outcome, match_over: bool) -> Menu<MenuAction> {
+        let current_match = &self.current_match;
+        let title = match (match_over, outcome, current_match.outcome()) {
+            (true, _, Outcome::Winner(player)) => format!("player {} wins the match!", player + 1),
+            (true, _, Outcome::Draw) => String::from("the match is a draw"),
+            (false, Outcome::Winner(player), _) => {
+                format!("round {}: player {}", current_match.round, player + 1)
             }
-            system_clock::wait(20);
+            (false, Outcome::Draw, _) => format!("round {}: draw", current_match.round),
+        };
+        let lines: Vec<(String, Color)> = self.snakes
+            .iter()
+            .enumerate()
+            .map(|(player, snake)| {
+                let wins = current_match.wins.get(player).cloned().unwrap_or(0);
+                let line = format!("player {}: {} apples, {} won", player + 1, snake.score, wins);
+                (line, self.theme.player_color(player))
+            })
+            .collect();
+        let (button, action) = if match_over {
+            ("new match", MenuAction::NewGame)
+        } else {
+            ("next round", MenuAction::NextRound)
+        };
+        render::scoreboard_menu(self.theme, (WIDTH, HEIGHT), &title, &lines, button, action)
+    }
 
+    /**
+     * Prints the score of every player on the screen, indented by `indent`.
+     */
+    fn print_scores(&self, indent: &str) {
+        if self.snakes.len() == 1 {
+            println!("{}score: {}", indent, self.snakes[0].score);
+            return;
         }
-        if new_game {
-            self.reset();
+        for (player, snake) in self.snakes.iter().enumerate() {
+            println!("{}player {}: {}", indent, player + 1, snake.score);
         }
     }
 
-    fn restart_game(&mut self) {
-        self.graphics.background_blink();
+    /**
+     * Writes the screen to `screenshot-<n>.bmp` on the debugger host. Takes
+     * a few seconds, the game stands still meanwhile.
+     */
+    fn take_screenshot(&mut self) {
+        let name = format!("screenshot-{}.bmp", self.screenshots);
+        self.screenshots += 1;
+        let mut file = match semihosting::File::create(&name) {
+            Ok(file) => file,
+            Err(()) => {
+                hprintln!("could not create {}", name);
+                return;
+            }
+        };
+        let mut result = Ok(());
+        self.graphics.screenshot(|bytes| {
+            if result.is_ok() {
+                result = file.write_all(bytes);
+            }
+        });
+        match result {
+            Ok(()) => hprintln!("saved {}", name),
+            Err(()) => hprintln!("could not write {}", name),
+        }
+    }
 
-        self.graphics.print_restart_screen();
-        println!("   score: {}", self.apple_counter);
-        self.apple_counter = 0;
-        let mut pause = true;
-        let mut new_game = false;
+    /**
+     * Handles touches on a menu on layer 2 until resume, new game or next
+     * round is clicked.
+     * The menu faces `self.side`.
+     */
+    fn run_menu(&mut self, menu: &mut Menu<MenuAction>) -> MenuAction {
+        let side = self.side;
         loop {
+            let warning = self.touch_warning;
+            // a finger stays pressed while reads fail, see `sample_touches`
             let touches = self.get_touches();
-
-            if touches.len() == 1 {
-                for touch in touches {
-                    let mut x = touch.0;
-                    let mut y = touch.1;
-
-                    if (x > 100 + 8 + 90 && x < 100 + 8 + 90 + 100)
-                        && (y > 6 + 139 && y < 6 + 139 + 30)
-                    {
-                        pause = false;
-                        break;
-                    }
-                    if (x > 100 + 8 + 78 && x < 100 + 8 + 90 + 120)
-                        && (y > 6 + 192 && y < 6 + 192 + 30)
-                    {
-                        new_game = true;
-                        break;
-                    }
+            // the menu only looks at the fingers that are down
+            self.touch_filter.take_taps();
+            let event = {
+                let mut layer = Rotated::new(&mut self.graphics.layer_2, side);
+                if warning && !self.touch_warning {
+                    // the warning covered part of the menu
+                    menu.draw(&mut layer);
                 }
+                let touch = if touches.len() == 1 {
+                    let point = (touches[0].0 as usize, touches[0].1 as usize);
+                    Some(layer.to_canvas_point(point))
+                } else {
+                    None
+                };
+                menu.touch(&mut layer, touch)
+            };
+            if event.is_some() {
+                self.play(mixer::Effect::Click);
             }
-            if !pause {
-                self.graphics.layer_2.clear();
-                break;
+            match event {
+                Some((MenuAction::SmoothMovement, Event::Toggled(on))) => {
+                    self.smooth_movement = on
+                }
+                Some((MenuAction::Arena, Event::Toggled(on))) => self.arena = on,
+                Some((MenuAction::TableTop, Event::Toggled(on))) => {
+                    self.table_top = on;
+                    self.update_zones();
+                }
+                Some((MenuAction::Goal, Event::Clicked)) => {
+                    self.rules.goal = self.rules.goal.next();
+                    let name = self.rules.goal.name();
+                    let mut layer = Rotated::new(&mut self.graphics.layer_2, side);
+                    menu.set_button_text(&mut layer, MenuAction::Goal, &name);
+                }
+                Some((MenuAction::Rounds, Event::Clicked)) => {
+                    self.rules.rounds = rules::next_rounds(self.rules.rounds);
+                    let name = rules::rounds_name(self.rules.rounds);
+                    let mut layer = Rotated::new(&mut self.graphics.layer_2, side);
+                    menu.set_button_text(&mut layer, MenuAction::Rounds, &name);
+                }
+                Some((MenuAction::Players, Event::Clicked)) => {
+                    self.players = self.players % MAX_PLAYERS + 1;
+                    let name = render::players_name(self.players);
+                    let mut layer = Rotated::new(&mut self.graphics.layer_2, side);
+                    menu.set_button_text(&mut layer, MenuAction::Players, name);
+                }
+                Some((MenuAction::BoardSize, Event::Clicked)) => {
+                    self.board = self.board.next();
+                    let name = self.board.name();
+                    let mut layer = Rotated::new(&mut self.graphics.layer_2, side);
+                    menu.set_button_text(&mut layer, MenuAction::BoardSize, name);
+                }
+                Some((MenuAction::Theme, Event::Clicked)) => {
+                    let theme = theme::next(self.theme);
+                    self.set_theme(theme);
+                    let mut layer = Rotated::new(&mut self.graphics.layer_2, side);
+                    menu.set_button_text(&mut layer, MenuAction::Theme, theme.name);
+                }
+                Some((MenuAction::Calibrate, Event::Clicked)) => {
+                    self.calibrate();
+                    self.graphics.layer_2.clear();
+                    if self.touch_warning {
+                        render::draw_touch_warning(&mut self.graphics.layer_2, true, self.theme.ui);
+                    }
+                    menu.draw(&mut Rotated::new(&mut self.graphics.layer_2, side));
+                }
+                Some((action @ MenuAction::Resume, Event::Clicked))
+                | Some((action @ MenuAction::NewGame, Event::Clicked))
+                | Some((action @ MenuAction::NextRound, Event::Clicked)) => return action,
+                _ => {}
             }
-            if new_game {
-                self.graphics.layer_2.clear();
-                self.graphics.layer_1.clear();
-                break;
+            self.wait_for_touch(20);
+        }
+    }
+
+    /**
+     * Shows the calibration screen on layer 2: the player taps a cross after
+     * another, then the calibration is fitted to the taps and saved. It stays
+     * as it was if the taps do not fit the crosses.
+     */
+    fn calibrate(&mut self) {
+        let targets = render::calibration_targets((WIDTH, HEIGHT));
+        // the points are read without the old calibration
+        let mut filter = TouchFilter::new();
+        let mut taps = Vec::new();
+        for (index, &target) in targets.iter().enumerate() {
+            let text = format!("tap the cross ({}/{})", index + 1, targets.len());
+            render::draw_calibration_screen(&mut self.graphics.layer_2, Some(target), &text);
+            loop {
+                if let Some(points) = self.read_touches() {
+                    filter.update(&points, system_clock::ticks());
+                }
+                // nothing calls `filter.touches`, every finger lifted is a tap
+                if let Some(&tap) = filter.take_taps().first() {
+                    taps.push(tap);
+                    self.play(mixer::Effect::Click);
+                    break;
+                }
+                self.wait_for_touch(20);
             }
         }
-        if new_game {
-            self.reset();
+        let text = match Calibration::from_taps(&targets, &taps) {
+            Some(calibration) => {
+                self.calibration = calibration;
+                settings::save_calibration(calibration);
+                "touch screen calibrated"
+            }
+            None => "the taps missed the crosses, try again",
+        };
+        render::draw_calibration_screen(&mut self.graphics.layer_2, None, text);
+        let shown_until = system_clock::ticks() + 1500;
+        while system_clock::ticks() < shown_until {
+            self.update_sound();
+            scheduler::sleep();
         }
+        // fingers found with the old calibration are somewhere else now
+        self.touch_filter.reset();
     }
+
     pub fn game_start_up(&mut self) {
-        self.graphics.print_bmp_at_with_rotaion(
-            self::graphics::WELCOME_SCREEN_BASE,
-            0,
-            0,
-            graphics::RotDirection::R0,
-        );
+        if let Some(ref mut sound) = self.sound {
+            if let Err(error) = sound.play_music(music::THEME) {
+                hprintln!("the theme has a mistake: {:?}", error);
+            }
+        }
+        render::draw_welcome_screen(&mut self.graphics.layer_1);
 
         let welcome = "Welcome to Mulity-Snake! Touch screen to start the game";
 
-        for c in welcome.chars() {
-            if c == ' ' || c == '-' || c == '!' {
-                print!("{}", c);
-            system_clock::wait(60);
-            } else {
-                self.graphics.print_bmp_at_downwards(
-                    self::graphics::WELCOME_SCREEN_OPEN_MOUTH,
-                    188,
-                    85,
-                );
-
-                print!("{}", c);
-                system_clock::wait(60);
-                self.graphics.print_bmp_at_downwards(
-                    self::graphics::WELCOME_SCREEN_CLOSED_MOUTH,
-                    188,
-                    85,
-                );
-            }
-        }
-        println!("");
+        // write the text one character every 60 ms while the snake talks,
+        // look for touches every 20 ms and sleep in between
+        let mut mouth = Animation::new(&render::WELCOME_MOUTH, Playback::Loop);
+        let mut chars = welcome.chars();
+        let now = system_clock::ticks();
+        mouth.start(now);
+        let mut next_char = now;
+        let mut next_poll = now;
         loop {
-            let touches = self.get_touches();
-            let mut pause = true;
-            if touches.len() == 1 {
-                self.graphics.layer_1.clear();
-                self.graphics.layer_2.clear();
-                break;
+            let now = system_clock::ticks();
+            if mouth.tick(now) {
+                let open = mouth.value().unwrap_or(false);
+                render::draw_welcome_mouth(&mut self.graphics.layer_1, open);
             }
-            system_clock::wait(20);
+            if mouth.is_running() && now >= next_char {
+                next_char += 60;
+                match chars.next() {
+                    Some(c) => print!("{}", c),
+                    None => {
+                        println!("");
+                        mouth.stop();
+                        render::draw_welcome_mouth(&mut self.graphics.layer_1, false);
+                    }
+                }
+            }
+            self.update_sound();
+            if now >= next_poll {
+                next_poll = now + 20;
+                if self.get_touches().len() == 1 {
+                    self.start_transition(Effect::Fade, false, ScreenChange::StartGame);
+                    break;
+                }
+            }
+            scheduler::sleep();
+        }
+    }
+    /**
+     * Puts a snake with `body_length` body segments for every player in the
+     * middle of the grid, one row every three cells, all moving to the right.
+     * The apple is at the left edge of the screen in the middle of the grid,
+     * in the row of the first one.
+     */
+    fn place_snakes(&mut self, body_length: usize) {
+        let middle = (self.grid_height - 1) / 2 - 3;
+        let first_row = middle.saturating_sub(3 * (self.players - 1) / 2).max(1);
+        let grid_width = self.grid_width;
+        self.snakes = (0..self.players)
+            .map(|player| Snake::new(first_row + 3 * player, body_length, grid_width))
+            .collect();
+        // in an arena the camera starts on the screen in the middle
+        let left = (grid_width - WIDTH / self.cell_size) / 2;
+        self.apple_position = (left + 1, first_row);
+        for player in 0..self.snakes.len() {
+            self.place_snake_tiles(player);
+        }
+    }
+
+    /**
+     * Writes the tiles of a snake that starts out moving to the right.
+     */
+    fn place_snake_tiles(&mut self, player: usize) {
+        let snake = &self.snakes[player];
+        let grid = &mut self.grid;
+        grid[snake.head.0][snake.head.1] = Tile::SnakeHead(Direction::right);
+        for body in &snake.body {
+            grid[body.0][body.1] = Tile::SnakeBody(Direction::right, Direction::right);
         }
+        grid[snake.tail.0][snake.tail.1] = Tile::SnakeTail(Direction::right);
     }
+
     pub fn reset(&mut self) {
-        let game_width = WIDTH / GRID_BLOCK_SIZE;
-        let game_height = HEIGHT / GRID_BLOCK_SIZE;
-        self.grid = vec![vec![Tile::Empty; game_height]; game_width];
-        self.grid[25][10] = Tile::SnakeHead(Direction::right);
-        self.snake_head_position = (25, 10);
-        self.snake_body_position = vec![(24, 10), (23, 10), (22, 10)];
-        self.snake_tail_position = (21, 10);
-        self.former_snake_tail = (20, 10);
-        self.apple_position = (1, 10);
-        self.draw_frame();
+        self.cell_size = self.board.cell_size();
+        let screens = if self.arena { ARENA_SCREENS } else { (1, 1) };
+        self.grid_width = WIDTH / self.cell_size * screens.0;
+        self.grid_height = HEIGHT / self.cell_size * screens.1;
+        self.grid = vec![vec![Tile::Empty; self.grid_height]; self.grid_width];
+        self.place_snakes(3);
+        self.update_zones();
+        self.round_started_at = system_clock::ticks();
+        self.camera = Camera::new((WIDTH, HEIGHT), self.world_size());
+        let head = self.head_pixel_position();
+        self.camera.center_on(head);
+        self.minimap = render::Minimap::new(WIDTH, (self.grid_width, self.grid_height));
+        self.minimap_dirty = true;
+        self.apple_animation.start(system_clock::ticks());
+        self.drawn_progress = render::PROGRESS_ONE;
+        self.full_redraw = true;
     }
 
+    /**
+     * Snakes that bit themselves or ran into another snake die. The last ones
+     * to die stay on the screen for the end of the round.
+     */
     pub fn check_selfbite(&mut self) {
-        for i in 0..self.snake_body_position.len() {
-            if self.snake_head_position == self.snake_body_position[i] {
-                self.restart_game();
-                return;
+        let bitten: Vec<usize> = (0..self.snakes.len())
+            .filter(|&player| self.snakes[player].alive && self.bites(player))
+            .collect();
+        let alive = self.snakes.iter().filter(|snake| snake.alive).count();
+        if !bitten.is_empty() {
+            self.play(mixer::Effect::Death);
+        }
+        for player in bitten.iter().cloned() {
+            if bitten.len() == alive {
+                self.snakes[player].alive = false;
+            } else {
+                self.kill_snake(player);
+            }
+        }
+    }
+
+    /**
+     * The head of the snake is on a snake, itself or another one.
+     */
+    fn bites(&self, player: usize) -> bool {
+        let head = self.snakes[player].head;
+        self.snakes
+            .iter()
+            .enumerate()
+            .filter(|&(_, snake)| snake.alive)
+            .any(|(other, snake)| {
+                (other != player && snake.head == head) || snake.body.contains(&head)
+                    || snake.tail == head
+            })
+    }
+
+    /**
+     * Takes a snake off the grid, its score stays.
+     */
+    fn kill_snake(&mut self, player: usize) {
+        self.snakes[player].alive = false;
+        let cells = self.snakes[player].cells();
+        let head = self.snakes[player].head;
+        let former_tail = self.snakes[player].former_tail;
+        for cell in cells {
+            // the head may be on another snake and did not take the tile
+            let own_tile = match self.grid[cell.0][cell.1] {
+                Tile::SnakeHead(_) => true,
+                _ => cell != head,
+            };
+            if own_tile {
+                self.grid[cell.0][cell.1] = Tile::Empty;
             }
+            self.mark_dirty(cell);
         }
-        if self.snake_head_position == self.snake_tail_position {
-            self.restart_game();
+        self.mark_dirty(former_tail);
+        self.minimap_dirty = true;
+    }
+
+    /**
+     * Starts a sound effect, if there is sound.
+     */
+    fn play(&mut self, effect: mixer::Effect) {
+        if let Some(ref mut sound) = self.sound {
+            sound.play(effect);
+        }
+    }
+
+    /**
+     * Keeps the sound going, call at least every 64 ms.
+     */
+    fn update_sound(&mut self) {
+        if let Some(ref mut sound) = self.sound {
+            sound.update();
         }
     }
 
     pub fn return_wait_tick(&mut self) -> usize {
 
         let mut tick:usize = 100;
+        let score = self.snakes.iter().map(|snake| snake.score).max().unwrap_or(0);
         
-        if let Some(new) = tick.checked_sub(self.apple_counter * 5) {
+        if let Some(new) = tick.checked_sub(score * 5) {
         
            return new
         }
@@ -686,3 +1525,116 @@ impl Game {
     }
 }
 
+impl Snake {
+    /**
+     * A snake with `body_length` body segments in `row`, moving to the right
+     * from the middle of a `grid_width` wide grid.
+     */
+    fn new(row: usize, body_length: usize, grid_width: usize) -> Snake {
+        let tail = grid_width / 2 - 3;
+        Snake {
+            head: (tail + body_length + 1, row),
+            body: (0..body_length)
+                .map(|i| (tail + body_length - i, row))
+                .collect(),
+            tail: (tail, row),
+            former_tail: (tail - 1, row),
+            former_tail_tile: Tile::SnakeTail(Direction::right),
+            direction: Direction::right,
+            score: 0,
+            alive: true,
+            head_animation: Animation::new(&render::HEAD_CHOMP, Playback::Once),
+        }
+    }
+
+    /**
+     * Head, body and tail.
+     */
+    fn cells(&self) -> Vec<(usize, usize)> {
+        let mut cells = Vec::with_capacity(self.body.len() + 2);
+        cells.push(self.head);
+        cells.extend_from_slice(&self.body);
+        cells.push(self.tail);
+        cells
+    }
+}
+
+impl Direction {
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::up => Direction::down,
+            Direction::down => Direction::up,
+            Direction::left => Direction::right,
+            Direction::right => Direction::left,
+        }
+    }
+
+    fn turned_left(self) -> Direction {
+        match self {
+            Direction::up => Direction::left,
+            Direction::down => Direction::right,
+            Direction::left => Direction::down,
+            Direction::right => Direction::up,
+        }
+    }
+
+    fn turned_right(self) -> Direction {
+        match self {
+            Direction::up => Direction::right,
+            Direction::down => Direction::left,
+            Direction::left => Direction::up,
+            Direction::right => Direction::down,
+        }
+    }
+}
+
+/**
+ * Picks the straight or corner sprite for a body segment and its rotation.
+ * The corner sprite connects the left and the bottom side of a cell.
+ */
+fn body_content(player: usize, entry: Direction, exit: Direction) -> render::CellContent {
+    if entry == exit {
+        let rot = match entry {
+            Direction::left | Direction::right => RotDirection::R0,
+            Direction::up | Direction::down => RotDirection::R90,
+        };
+        return render::CellContent::Body {
+            player: player,
+            corner: false,
+            rot: rot,
+        };
+    }
+    // the sides of the cell the segment connects
+    let sides = (entry.opposite(), exit);
+    let rot = match sides {
+        (Direction::left, Direction::down) | (Direction::down, Direction::left) => {
+            RotDirection::R0
+        }
+        (Direction::left, Direction::up) | (Direction::up, Direction::left) => RotDirection::R90,
+        (Direction::up, Direction::right) | (Direction::right, Direction::up) => {
+            RotDirection::R180
+        }
+        _ => RotDirection::R270,
+    };
+    render::CellContent::Body {
+        player: player,
+        corner: true,
+        rot: rot,
+    }
+}
+
+/**
+ * The tail sprite points to the left and connects to the right side of a cell.
+ */
+fn tail_content(player: usize, exit: Direction) -> render::CellContent {
+    let rot = match exit {
+        Direction::right => RotDirection::R0,
+        Direction::down => RotDirection::R90,
+        Direction::left => RotDirection::R180,
+        Direction::up => RotDirection::R270,
+    };
+    render::CellContent::Tail {
+        player: player,
+        rot: rot,
+    }
+}
@@ -1,32 +1,61 @@
-use stm32f7::{lcd, system_clock};
+use canvas::{Canvas, Color, ToColor};
+use core::mem;
+use core::ptr;
+use screenshot;
+use theme;
+use stm32f7::lcd;
+use transition::{Effect, COVERED};
+use profile::{self, Layer1Format, Layer2Format};
+
+pub use sprite::RotDirection;
+
+use super::HEIGHT;
+use super::WIDTH;
 
 pub struct Graphics {
     lcd: lcd::Lcd,
-    pub layer_1: lcd::Layer<lcd::FramebufferArgb8888>,
-    pub layer_2: lcd::Layer<lcd::FramebufferAl88>,
+    pub layer_1: lcd::Layer<Layer1Format>,
+    pub layer_2: lcd::Layer<Layer2Format>,
+    /// window registers of both layers as set up by the lcd driver
+    windows: [LayerWindow; 2],
+    /// shows wherever both layers are transparent
+    background: Color,
+}
+
+/// The part of an LTDC layer that is shown, see `Graphics::shift_layer`.
+#[derive(Clone, Copy)]
+struct LayerWindow {
+    whpcr: u32,
+    cfbar: u32,
+    cfblr: u32,
 }
 
-pub enum RotDirection {
-    R0,
-    R90,
-    R180,
-    R270,
+/// Base address of the LTDC registers.
+const LTDC_START: usize = 0x4001_6800;
+/// Shadow reload configuration register.
+const LTDC_SRCR: usize = LTDC_START + 0x24;
+/// Reload the shadow registers during the next vertical blanking.
+const SRCR_VBR: u32 = 1 << 1;
+/// Offset between the registers of layer 1 and layer 2.
+const LAYER_REGISTERS_STRIDE: usize = 0x80;
+/// Layer 1 window horizontal position configuration register.
+const L1WHPCR: usize = LTDC_START + 0x88;
+/// Layer 1 constant alpha configuration register.
+const L1CACR: usize = LTDC_START + 0x98;
+/// Layer 1 color frame buffer address register.
+const L1CFBAR: usize = LTDC_START + 0xac;
+/// Layer 1 color frame buffer length register.
+const L1CFBLR: usize = LTDC_START + 0xb0;
+
+fn layer_register(register: usize, layer: usize) -> *mut u32 {
+    (register + layer * LAYER_REGISTERS_STRIDE) as *mut u32
 }
 
-pub const PAUSE_SCREEN_RIGHT: &[u8] = include_bytes!("../assets/Pause_screen_snake_left.bmp");
-pub const PAUSE_SCREEN_LEFT: &[u8] = include_bytes!("../assets/Pause_screen_snake_right.bmp");
-pub const PAUSE_SCREEN_GAME_OVER: &[u8] = include_bytes!("../assets/Pause_screen_game_over.bmp");
-pub const PAUSE_SCREEN_PAUSE: &[u8] = include_bytes!("../assets/Pause_screen_pause.bmp");
-pub const PAUSE_SCREEN_RESUME: &[u8] = include_bytes!("../assets/Pause_screen_resume.bmp");
-pub const PAUSE_SCREEN_NEW_GAME: &[u8] = include_bytes!("../assets/Pause_screen_New_game.bmp");
-pub const WELCOME_SCREEN_BASE: &[u8] = include_bytes!("../assets/Welcom_screen/Snake_base2.bmp");
-pub const WELCOME_SCREEN_OPEN_MOUTH: &[u8] =
-    include_bytes!("../assets/Welcom_screen/Snake_mouth_open.bmp");
-pub const WELCOME_SCREEN_CLOSED_MOUTH: &[u8] =
-    include_bytes!("../assets/Welcom_screen/Snake_mouth_shut.bmp");
-pub const APPLE_BMP: &[u8] = include_bytes!("../assets/apple.bmp");
-pub const SNAKE_MOUTH_CLOSED: &[u8] = include_bytes!("../assets/snake_head_closed.bmp");
-pub const SNAKE_MOUTH_OPEN: &[u8] = include_bytes!("../assets/snake_head_opened.bmp");
+/// Bytes per pixel of layer 1 and layer 2.
+const LAYER_BYTES_PER_PIXEL: [u32; 2] = [
+    profile::LAYER_1_BYTES_PER_PIXEL as u32,
+    profile::LAYER_2_BYTES_PER_PIXEL as u32,
+];
 
 impl Graphics {
     /**
@@ -43,43 +72,200 @@ impl Graphics {
         lcd::init_stdout(layer_2_copy);
 
         // assignments to our struct
+        let mut windows = [LayerWindow {
+            whpcr: 0,
+            cfbar: 0,
+            cfblr: 0,
+        }; 2];
+        for (layer, window) in windows.iter_mut().enumerate() {
+            unsafe {
+                window.whpcr = ptr::read_volatile(layer_register(L1WHPCR, layer));
+                window.cfbar = ptr::read_volatile(layer_register(L1CFBAR, layer));
+                window.cfblr = ptr::read_volatile(layer_register(L1CFBLR, layer));
+            }
+        }
+        // `LayerMemory` writes to the framebuffers without asking the driver
+        let layers = [
+            (profile::LAYER_1_START, profile::LAYER_1_BYTES_PER_PIXEL),
+            (profile::LAYER_2_START, profile::LAYER_2_BYTES_PER_PIXEL),
+        ];
+        for (layer, (window, &(start, bytes))) in windows.iter().zip(layers.iter()).enumerate() {
+            assert_eq!(window.cfbar as usize, start, "layer {} framebuffer moved", layer + 1);
+            // the pitch, bytes from the start of a row to the next one
+            let pitch = (window.cfblr >> 16) & 0x1fff;
+            assert_eq!(pitch as usize, WIDTH * bytes, "layer {} is not {} wide", layer + 1, WIDTH);
+        }
+        assert_eq!(mem::size_of::<<Layer1Format as PixelFormat>::Pixel>(), layers[0].1);
+        assert_eq!(mem::size_of::<<Layer2Format as PixelFormat>::Pixel>(), layers[1].1);
         let mut graphics = Graphics {
             lcd: lcd,
             layer_1: layer_1,
             layer_2: layer_2,
+            windows: windows,
+            background: theme::CLASSIC.background,
         };
         // clear and set black background
         graphics.layer_1.clear();
         graphics.layer_2.clear();
+        let background = graphics.background;
+        graphics.set_background(background);
         graphics
-            .lcd
-            .set_background_color(lcd::Color::from_hex(0xb07708)); // snake color
-        graphics
     }
-    pub fn background_blink(&mut self) {
-        for i in 0..6 {
-            self.lcd.set_background_color(lcd::Color::rgb(255, 0, 0));
-            system_clock::wait(50);
-            self.lcd
-                .set_background_color(lcd::Color::from_hex(0xb07708));
-                system_clock::wait(50);
+
+    /**
+     * Sets the color shown behind both layers.
+     */
+    pub fn set_background(&mut self, color: Color) {
+        self.background = color;
+        self.lcd.set_background_color(to_lcd_color(color));
+    }
+
+    /**
+     * Lets the background flash in `color` a few times, `wait` is called
+     * with the ms to wait in between.
+     */
+    pub fn background_blink<W: FnMut(usize)>(&mut self, color: Color, mut wait: W) {
+        for _ in 0..6 {
+            self.lcd.set_background_color(to_lcd_color(color));
+            wait(50);
+            self.lcd.set_background_color(to_lcd_color(self.background));
+            wait(50);
         }
     }
+
+    /**
+     * Encodes what the display currently shows as a BMP file and passes it to
+     * `write` in pieces, see `screenshot::encode`.
+     */
+    pub fn screenshot<W: FnMut(&[u8])>(&self, write: W) {
+        let background = self.background;
+        let pixel = |x: usize, y: usize| {
+            screenshot::composite(
+                background,
+                Layer1Format::read_pixel(x, y).to_color(),
+                Layer2Format::read_pixel(x, y).to_color(),
+            )
+        };
+        screenshot::encode(WIDTH, HEIGHT, pixel, write);
+    }
+
     /**
-     * prints a square in defined size+color at position x,y
+     * Moves the content of layer 1 by `dx`, `dy` pixels, e.g. (-2, 0) moves
+     * it two pixels to the left. What moves off the screen is lost and the
+     * uncovered strips keep their old pixels, they have to be redrawn.
      */
-    pub fn print_square_size_color_at(
-        &mut self,
-        x: usize,
-        y: usize,
-        size: usize,
-        color: lcd::Color,
-    ) {
-        for x in x..(x + size) {
-            for y in y..(y + size) {
-                self.layer_1.print_point_color_at(x, y, color);
+    pub fn scroll_layer_1(&mut self, dx: isize, dy: isize) {
+        let (shift_x, shift_y) = (dx.abs() as usize, dy.abs() as usize);
+        if shift_x >= WIDTH || shift_y >= HEIGHT {
+            return;
+        }
+        let bytes = profile::LAYER_1_BYTES_PER_PIXEL;
+        let (from_x, to_x) = if dx < 0 { (shift_x, 0) } else { (0, shift_x) };
+        let length = (WIDTH - shift_x) * bytes;
+        let rows = HEIGHT - shift_y;
+        for i in 0..rows {
+            // rows moving down are copied bottom up, so none is overwritten
+            // before it was copied
+            let (from_y, to_y) = if dy > 0 {
+                (rows - 1 - i, HEIGHT - 1 - i)
+            } else {
+                (shift_y + i, i)
+            };
+            let from = profile::LAYER_1_START + (from_y * WIDTH + from_x) * bytes;
+            let to = profile::LAYER_1_START + (to_y * WIDTH + to_x) * bytes;
+            // a memmove, far faster than going pixel by pixel
+            unsafe { ptr::copy(from as *const u8, to as *mut u8, length) };
+        }
+    }
+
+    /**
+     * Shows a transition with the given coverage (0 = screen fully visible,
+     * `COVERED` = only the background is left). Only the LTDC registers are
+     * changed, the framebuffers stay untouched.
+     */
+    pub fn apply_transition(&mut self, effect: Effect, coverage: u32, overlay_only: bool) {
+        let first_layer = if overlay_only { 1 } else { 0 };
+        for layer in first_layer..2 {
+            match effect {
+                Effect::Fade => {
+                    let alpha = 255 - coverage * 255 / COVERED;
+                    self.set_layer_alpha(layer, alpha as u8);
+                }
+                Effect::Wipe => self.shift_layer(layer, coverage, true),
+                Effect::Slide => self.shift_layer(layer, coverage, false),
+            }
+        }
+        if effect == Effect::Fade && !overlay_only {
+            // the background fades to black as well
+            let background = self.background;
+            let scale = |channel: u8| (u32::from(channel) * (COVERED - coverage) / COVERED) as u8;
+            self.lcd.set_background_color(lcd::Color::rgb(
+                scale(background.red),
+                scale(background.green),
+                scale(background.blue),
+            ));
+        }
+    }
+
+    /**
+     * Restores the layer registers and the background color after a transition.
+     */
+    pub fn end_transition(&mut self) {
+        for layer in 0..2 {
+            let window = self.windows[layer];
+            unsafe {
+                ptr::write_volatile(layer_register(L1WHPCR, layer), window.whpcr);
+                ptr::write_volatile(layer_register(L1CFBAR, layer), window.cfbar);
+                ptr::write_volatile(layer_register(L1CFBLR, layer), window.cfblr);
             }
+            self.set_layer_alpha(layer, 255);
         }
+        self.lcd.set_background_color(to_lcd_color(self.background));
+    }
+
+    /**
+     * Sets the constant alpha the pixels of a layer (0 = layer 1, 1 = layer 2)
+     * are multiplied with.
+     */
+    fn set_layer_alpha(&mut self, layer: usize, alpha: u8) {
+        unsafe {
+            ptr::write_volatile(layer_register(L1CACR, layer), u32::from(alpha));
+            ptr::write_volatile(LTDC_SRCR as *mut u32, SRCR_VBR);
+        }
+    }
+
+    /**
+     * Moves the left edge of a layer window to the right by `coverage` of the
+     * screen width. With `keep_content` the pixels stay where they are and
+     * the background is uncovered (wipe), otherwise the layer moves with its
+     * window (slide).
+     */
+    fn shift_layer(&mut self, layer: usize, coverage: u32, keep_content: bool) {
+        let window = self.windows[layer];
+        let start = window.whpcr & 0xfff;
+        let stop = (window.whpcr >> 16) & 0xfff;
+        let offset = (stop + 1 - start) * coverage / COVERED;
+        if start + offset > stop {
+            // the window can not be empty, hide the layer instead
+            self.set_layer_alpha(layer, 0);
+            return;
+        }
+        let bytes = LAYER_BYTES_PER_PIXEL[layer];
+        let address = if keep_content {
+            window.cfbar + offset * bytes
+        } else {
+            window.cfbar
+        };
+        let line_length = (stop + 1 - start - offset) * bytes + 3;
+        unsafe {
+            ptr::write_volatile(layer_register(L1WHPCR, layer), stop << 16 | (start + offset));
+            ptr::write_volatile(layer_register(L1CFBAR, layer), address);
+            ptr::write_volatile(
+                layer_register(L1CFBLR, layer),
+                (window.cfblr & 0xffff_0000) | line_length,
+            );
+        }
+        self.set_layer_alpha(layer, 255);
     }
 
     // pub fn print_string_at(&mut self,x:usize,y:usize,string: &str){
@@ -87,207 +273,111 @@ impl Graphics {
     //     text.layer(&self).write_str(string);
 
     // }
+}
+
+/**
+ * converts a color to the type of the lcd driver
+ */
+pub fn to_lcd_color(color: Color) -> lcd::Color {
+    lcd::Color {
+        red: color.red,
+        green: color.green,
+        blue: color.blue,
+        alpha: color.alpha,
+    }
+}
+
+/**
+ * How a color is stored in a framebuffer of this format.
+ */
+pub trait PixelFormat {
+    type Pixel: Copy + ToColor;
+
+    fn pixel(color: Color) -> Self::Pixel;
+}
+
+impl PixelFormat for lcd::FramebufferArgb8888 {
+    type Pixel = u32;
+
+    fn pixel(color: Color) -> u32 {
+        (color.alpha as u32) << 24 | (color.red as u32) << 16 | (color.green as u32) << 8
+            | color.blue as u32
+    }
+}
+
+impl PixelFormat for lcd::FramebufferAl88 {
+    type Pixel = u16;
+
+    fn pixel(color: Color) -> u16 {
+        (color.alpha as u16) << 8 | color.luminance() as u16
+    }
+}
+
+/**
+ * Direct access to the framebuffer memory of a layer, so whole rows can be
+ * written without going through `print_point_color_at` for every pixel.
+ * Implemented for the formats the profile picks for the layers, which tells
+ * the layer apart by its format.
+ */
+pub trait LayerMemory: PixelFormat {
+    /// start of the framebuffer, see `profile`
+    const START: usize;
+
     /**
-     * prints a pixels from a slice of a bitmap at position x,y on layer1 with rotation of 0,90,180,270 degree
-     * Bitmaps need to be in 24bit color depth and uncompressed
+     * Writes `len` pixels of row y, starting at x. The span must be on the screen.
      */
-    pub fn print_bmp_at_with_rotaion(&mut self, pic: &[u8], x: u32, y: u32, rot: RotDirection) {
-        let pixels_start = pic[10] as u32;
-        let width = (pic[18] as u32) + ((pic[19] as u32) * 256_u32);
-        let height = (pic[22] as u32) + ((pic[23] as u32) * 256_u32);
-        let pixel_rest = width % 4; // If width isn't dividable by 4 the lines are filled with bytes of zeros
-
-        let rot_width = height;
-        let rot_height = width;
-
-        let at_x = x;
-        let mut at_y = y;
-        let mut bytenr: u32 = pixels_start;
-        let pic_length: u32 = pic.len() as u32;
-
-        match rot {
-            RotDirection::R0 => {
-                at_y = y + height - 1;
-            }
-            RotDirection::R90 => {
-                at_y = y + rot_height - 1;
-            }
-            RotDirection::R180 => {
-                at_y = y + height - 1;
-                bytenr = pic_length - 1;
-            }
-            RotDirection::R270 => {
-                at_y = y + rot_height - 1;
-            }
+    fn write_span(x: usize, y: usize, len: usize, color: Color) {
+        let value = Self::pixel(color);
+        let row = (Self::START as *mut Self::Pixel).wrapping_offset((y * WIDTH + x) as isize);
+        for i in 0..len {
+            unsafe { ptr::write_volatile(row.offset(i as isize), value) };
         }
+    }
 
-        match rot {
-            RotDirection::R0 => for i in 0..height {
-                for j in 0..width {
-                    if pic[(bytenr + 2) as usize] > 245 && pic[(bytenr + 1) as usize] > 245
-                        && pic[(bytenr) as usize] > 245
-                    {
-
-                    } else {
-                        self.layer_1.print_point_color_at(
-                            (j + at_x) as usize,
-                            (at_y - i) as usize,
-                            lcd::Color::rgb(
-                                pic[bytenr as usize + 2],
-                                pic[(bytenr + 1) as usize],
-                                pic[(bytenr) as usize],
-                            ),
-                        );
-                    }
-                    bytenr = bytenr + 3;
-                    if j == (width - 1) {
-                        bytenr = bytenr + pixel_rest;
-                    }
-                }
-            },
-            RotDirection::R90 => for i in 0..rot_height {
-                bytenr = pixels_start + width * 3 - 3 * (i + 1);
-                for j in 0..rot_width {
-                    if pic[(bytenr + 2) as usize] > 245 && pic[(bytenr + 1) as usize] > 245
-                        && pic[(bytenr) as usize] > 245
-                    {
-
-                    } else {
-                        self.layer_1.print_point_color_at(
-                            (j + at_x) as usize,
-                            (at_y - i) as usize,
-                            lcd::Color::rgb(
-                                pic[(bytenr + 2) as usize],
-                                pic[(bytenr + 1) as usize],
-                                pic[(bytenr) as usize],
-                            ),
-                        );
-                    }
-                    bytenr = bytenr + width * 3 + pixel_rest;
-                }
-            },
-            RotDirection::R180 => for i in 0..height {
-                bytenr = bytenr - pixel_rest;
-                for j in 0..width {
-                    if pic[(bytenr - 2) as usize] > 245 && pic[(bytenr - 1) as usize] > 245
-                        && pic[(bytenr) as usize] > 245
-                    {
-
-                    } else {
-                        self.layer_1.print_point_color_at(
-                            (j + at_x) as usize,
-                            (at_y - i) as usize,
-                            lcd::Color::rgb(
-                                pic[(bytenr) as usize],
-                                pic[(bytenr - 1) as usize],
-                                pic[(bytenr - 2) as usize],
-                            ),
-                        );
-                    }
-                    bytenr = bytenr - 3;
-                }
-            },
-            RotDirection::R270 => for i in 0..rot_height {
-                bytenr = pixels_start + (height - 1) * (3 * width + pixel_rest) + i * 3;
-
-                for j in 0..rot_width {
-                    if pic[(bytenr + 2) as usize] > 245 && pic[(bytenr + 1) as usize] > 245
-                        && pic[(bytenr) as usize] > 245
-                    {
-
-                    } else {
-                        self.layer_1.print_point_color_at(
-                            (j + at_x) as usize,
-                            (at_y - i) as usize,
-                            lcd::Color::rgb(
-                                pic[(bytenr + 2) as usize],
-                                pic[(bytenr + 1) as usize],
-                                pic[(bytenr) as usize],
-                            ),
-                        );
-                    }
-                    if bytenr > ((3 * width) + pixel_rest) {
-                        bytenr = bytenr - (3 * width) - pixel_rest;
-                    };
-                }
-            },
-        }
+    /**
+     * The pixel at x, y, which must be on the screen.
+     */
+    fn read_pixel(x: usize, y: usize) -> Self::Pixel {
+        let pixel = (Self::START as *const Self::Pixel).wrapping_offset((y * WIDTH + x) as isize);
+        unsafe { ptr::read_volatile(pixel) }
     }
-    pub fn print_bmp_at_downwards(&mut self, pic: &[u8], x: u32, y: u32) {
-        let pixels_start = pic[10] as u32;
-        let width = (pic[18] as u32) + ((pic[19] as u32) * 256_u32);
-        let height = (pic[22] as u32) + ((pic[23] as u32) * 256_u32);
-        let pixel_rest = width % 4;
-
-        let at_x = x;
-        let mut at_y = y;
-        let mut bytenr: u32 = pixels_start;
-        let pixel_end: u32 = pic.len() as u32 - 1;
-        // println!("{},{}",pixel_rest,pixel_end );
-
-        for i in 0..height {
-            bytenr = pixel_end + 1 - (pixel_rest + width * 3) * (i + 1);
-            for j in 0..width {
-                // println!("{}",bytenr );
-                self.layer_1.print_point_color_at(
-                    (j + at_x) as usize,
-                    (at_y + i) as usize,
-                    lcd::Color::rgb(
-                        pic[(bytenr + 2) as usize],
-                        pic[(bytenr + 1) as usize],
-                        pic[(bytenr) as usize],
-                    ),
-                );
-                bytenr = bytenr + 3;
-            }
-        }
+}
+
+impl LayerMemory for Layer1Format {
+    const START: usize = profile::LAYER_1_START;
+}
+
+impl LayerMemory for Layer2Format {
+    const START: usize = profile::LAYER_2_START;
+}
+
+/**
+ * Both LTDC layers can be drawn on directly.
+ */
+impl<T: lcd::Framebuffer + LayerMemory> Canvas for lcd::Layer<T> {
+    fn width(&self) -> usize {
+        WIDTH
     }
-    pub fn print_bmp_at_layer2(&mut self, pic: &[u8], x: u32, y: u32) {
-        let pixels_start = pic[10] as u32;
-        let width = (pic[18] as u32) + ((pic[19] as u32) * 256_u32);
-        let height = (pic[22] as u32) + ((pic[23] as u32) * 256_u32);
-        let pixel_rest = width % 4;
-
-        let at_x = x;
-        let mut at_y = y;
-        let mut bytenr: u32 = pixels_start;
-        let pixel_end: u32 = pic.len() as u32 - 1;
-        // println!("{},{}",pixel_rest,pixel_end );
-
-        for i in 0..height {
-            bytenr = pixel_end + 1 - (pixel_rest + width * 3) * (i + 1);
-            for j in 0..width {
-                if pic[(bytenr + 2) as usize] > 245 && pic[(bytenr + 1) as usize] > 245
-                    && pic[(bytenr) as usize] > 245
-                {
-
-                } else {
-                    self.layer_2.print_point_color_at(
-                        (j + at_x) as usize,
-                        (at_y + i) as usize,
-                        lcd::Color::rgba(
-                            pic[(bytenr + 2) as usize],
-                            pic[(bytenr + 1) as usize],
-                            pic[(bytenr) as usize],
-                            pic[(bytenr) as usize]-50,
-                        ),
-                    );
-                }
-                bytenr = bytenr + 3;
-            }
+
+    fn height(&self) -> usize {
+        HEIGHT
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        // not `print_point_color_at`, the driver keeps only the red channel
+        // for AL88
+        if x < WIDTH && y < HEIGHT {
+            T::write_span(x, y, 1, color);
         }
     }
-    pub fn print_pause_screen(&mut self) {
-        self.print_bmp_at_layer2(PAUSE_SCREEN_LEFT, 90 + 8, 20);
-        self.print_bmp_at_layer2(PAUSE_SCREEN_RIGHT, 90 + 100 + 102 + 8, 20);
-        self.print_bmp_at_layer2(PAUSE_SCREEN_PAUSE, 100 + 90 + 8, 10 + 45);
-        self.print_bmp_at_layer2(PAUSE_SCREEN_RESUME, 100 + 8 + 90, 139 + 6);
-        self.print_bmp_at_layer2(PAUSE_SCREEN_NEW_GAME, 100 + 8 + 78, 192 + 6);
+
+    fn fill_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        if x < WIDTH && y < HEIGHT {
+            T::write_span(x, y, len.min(WIDTH - x), color);
+        }
     }
-    pub fn print_restart_screen(&mut self) {
-        self.print_bmp_at_layer2(PAUSE_SCREEN_LEFT, 60 + 8, 20);
-        self.print_bmp_at_layer2(PAUSE_SCREEN_RIGHT, 90 + 100 + 20 + 102 + 8, 20);
-        self.print_bmp_at_layer2(PAUSE_SCREEN_GAME_OVER, 60 + 90 + 8, 10 + 45);
-        self.print_bmp_at_layer2(PAUSE_SCREEN_NEW_GAME, 100 + 8 + 78, 192 + 6);
+
+    fn clear(&mut self) {
+        lcd::Layer::clear(self);
     }
 }
@@ -17,14 +17,38 @@ extern crate r0;
 extern crate smoltcp;
 
 #[macro_use]
-use stm32f7::{board, embedded, lcd, sdram, system_clock, touch, i2c};
-
+use stm32f7::{audio, board, embedded, interrupts, lcd, sdram, system_clock, i2c};
+use stm32f7::interrupts::{InterruptRequest, Priority};
+
+mod animation;
+mod assets;
+mod camera;
+mod canvas;
+mod controls;
+mod font;
+mod frame_timer;
 mod game;
 mod graphics;
+mod mixer;
+mod music;
+mod profile;
 mod random;
-
-pub const HEIGHT: usize = 272;
-pub const WIDTH: usize = 480;
+mod render;
+mod rle;
+mod rules;
+mod scheduler;
+mod screenshot;
+mod semihosting;
+mod settings;
+mod sound;
+mod sprite;
+mod theme;
+mod touch_filter;
+mod touchscreen;
+mod transition;
+mod ui;
+
+pub use profile::{HEIGHT, WIDTH};
 
 #[no_mangle]
 pub unsafe extern "C" fn reset() -> ! {
@@ -71,9 +95,11 @@ fn main(hw: board::Hardware) -> ! {
         gpio_j,
         gpio_k,
         syscfg,
+        nvic,
         ethernet_mac,
         ethernet_dma,
         i2c_3,
+        sai_2,
         rng,
         ..
     } = hw;
@@ -123,32 +149,83 @@ fn main(hw: board::Hardware) -> ! {
 
     system_clock::wait(200);
 
-    touch::check_family_id(&mut i2c_3).unwrap();
+    // the touch controller wakes the cpu, otherwise it is polled
+    let touch_interrupt = scheduler::init_touch_interrupt(&mut i2c_3);
+    if let Err(ref error) = touch_interrupt {
+        hprintln!("no touch interrupt, polling the touch screen: {:?}", error);
+    }
+
+    // sound effects on the headphone jack, the game is silent without them
+    audio::init_sai_2_pins(&mut gpio);
+    audio::init_sai_2(sai_2, rcc);
+    let codec = audio::init_wm8994(&mut i2c_3);
+    let sound = match codec.and_then(|_| sound::Sound::new(&mut i2c_3)) {
+        Ok(sound) => Some(sound),
+        Err(error) => {
+            hprintln!("no sound, the audio codec failed: {:?}", error);
+            None
+        }
+    };
 
     /* ETHERNET START */
 
     /* ETHERNET END */
     // l0et layer2 = lcd::Layer<lcd::FramebufferAl88>;
 
+    // a touch screen that does not answer shows a warning instead of a panic
+    let mut touch_screen = touchscreen::TouchScreen::new(i2c_3);
+    if let Err(error) = touch_screen.check() {
+        hprintln!("the touch screen does not answer: {:?}", error);
+    }
+
+    // the calibration of the last run is kept in the backup SRAM
+    settings::init();
+
     let random_gen = random::Random::new(rng, rcc);
     // Initialize Game
-    let mut game = game::Game::new(graphics, i2c_3, random_gen);
-    gameloop(game);
+    let game = game::Game::new(graphics, touch_screen, random_gen, sound);
+    interrupts::scope(nvic, |_| {}, |interrupt_table| {
+        if touch_interrupt.is_ok() {
+            interrupt_table
+                .register(
+                    InterruptRequest::Exti15to10,
+                    Priority::P1,
+                    scheduler::on_touch_interrupt,
+                )
+                .expect("the touch interrupt is taken");
+        }
+        gameloop(game)
+    })
 }
 
 fn gameloop(mut game: game::Game) -> ! {
     game.game_start_up();
-    game.draw_frame();
 
+    // the snake moves every tick, animations and transitions are updated in
+    // between, once per frame; the cpu sleeps until whatever comes first
+    let mut next_tick = system_clock::ticks();
     loop {
-        // let ticks = system_clock::ticks();
-        game.move_snake();
-        game.check_grid_edge();        
-        game.snake_bite();
-        game.check_selfbite();
+        let next_frame = system_clock::ticks() + scheduler::FRAME_DURATION;
+        // the touch filter needs a sample every frame to see how long
+        // fingers stay
+        game.sample_touches();
+        // the snake waits while a transition sets up a new screen
+        if system_clock::ticks() >= next_tick && !game.is_changing_screen() {
+            game.move_snake();
+            game.check_grid_edge();
+            game.snake_bite();
+            game.check_selfbite();
+            game.check_round_end();
+            let duration = 100 + game.return_wait_tick();
+            next_tick = system_clock::ticks() + duration;
+            game.start_step(duration);
+        }
+        game.animate();
         game.draw_game();
-        system_clock::wait(100);
-
-        system_clock::wait(game.return_wait_tick());
+        if game.is_changing_screen() {
+            scheduler::sleep_until(next_frame);
+        } else {
+            scheduler::sleep_until(next_tick.min(next_frame));
+        }
     }
 }
@@ -0,0 +1,354 @@
+//! Sound effects and music, synthesized and mixed into 16 bit stereo samples.
+//!
+//! Nothing in here touches the hardware: `sound` streams what the `Mixer`
+//! produces to the codec, on a PC it just fills buffers.
+
+use music::{Sequencer, SongError};
+
+/// Samples per second and channel, the rate the codec is set up for.
+pub const SAMPLE_RATE: u32 = 16_000;
+/// Range of a 16 bit sample, the mix is clipped to it.
+const SAMPLE_MIN: i32 = -32_768;
+const SAMPLE_MAX: i32 = 32_767;
+
+/// Effects that play at the same time, another one replaces the oldest.
+const VOICES: usize = 4;
+
+/**
+ * The sounds of the game.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Effect {
+    Eat,
+    Turn,
+    Death,
+    Click,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    /// pseudo random, the frequency sets how often it changes
+    Noise,
+}
+
+/**
+ * A part of an effect: a tone that slides from `from` to `to` Hz and fades
+ * out linearly over `duration` ms, starting at `volume`.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Tone {
+    pub waveform: Waveform,
+    pub from: u32,
+    pub to: u32,
+    pub duration: u32,
+    pub volume: i16,
+}
+
+macro_rules! tone {
+    ($waveform:ident, $from:expr, $to:expr, $duration:expr, $volume:expr) => {
+        Tone {
+            waveform: Waveform::$waveform,
+            from: $from,
+            to: $to,
+            duration: $duration,
+            volume: $volume,
+        }
+    };
+}
+
+static EAT: [Tone; 2] = [
+    tone!(Square, 660, 880, 40, 6000),
+    tone!(Square, 990, 1320, 60, 6000),
+];
+static TURN: [Tone; 1] = [tone!(Square, 440, 520, 15, 2500)];
+static DEATH: [Tone; 2] = [
+    tone!(Square, 440, 110, 400, 7000),
+    tone!(Noise, 4000, 1000, 250, 5000),
+];
+static CLICK: [Tone; 1] = [tone!(Square, 1200, 1200, 10, 4000)];
+
+impl Effect {
+    pub fn tones(self) -> &'static [Tone] {
+        match self {
+            Effect::Eat => &EAT,
+            Effect::Turn => &TURN,
+            Effect::Death => &DEATH,
+            Effect::Click => &CLICK,
+        }
+    }
+}
+
+/**
+ * Generates a waveform, one sample after another.
+ */
+#[derive(Clone, Copy)]
+pub struct Oscillator {
+    /// position in the period of the waveform, a full period is 2^32
+    phase: u32,
+    /// state of the noise generator, a 16 bit Galois LFSR
+    noise: u16,
+}
+
+impl Oscillator {
+    pub fn new() -> Oscillator {
+        Oscillator {
+            phase: 0,
+            noise: 0xace1,
+        }
+    }
+
+    /**
+     * The next sample of `waveform` at a frequency in 1/100 Hz, between
+     * `-amplitude` and `amplitude`.
+     */
+    pub fn next(&mut self, waveform: Waveform, centihertz: u32, amplitude: i32) -> i32 {
+        let step = (u64::from(centihertz) << 32) / u64::from(SAMPLE_RATE * 100);
+        let (phase, wrapped) = self.phase.overflowing_add(step as u32);
+        self.phase = phase;
+        match waveform {
+            Waveform::Square => if phase < 1 << 31 {
+                amplitude
+            } else {
+                -amplitude
+            },
+            Waveform::Triangle => {
+                // rises over the first half of the period, falls over the second
+                let rising = if phase < 1 << 31 { phase } else { !phase };
+                let level = i64::from(rising >> 15) - (1 << 15);
+                ((i64::from(amplitude) * level) >> 15) as i32
+            }
+            Waveform::Noise => {
+                if wrapped {
+                    let bit = self.noise & 1;
+                    self.noise >>= 1;
+                    if bit == 1 {
+                        self.noise ^= 0xb400;
+                    }
+                }
+                if self.noise & 1 == 1 {
+                    amplitude
+                } else {
+                    -amplitude
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Plays the tones of one effect after another.
+ */
+#[derive(Clone, Copy)]
+struct Voice {
+    tones: &'static [Tone],
+    tone: usize,
+    /// samples played of the current tone
+    position: u32,
+    oscillator: Oscillator,
+}
+
+impl Voice {
+    fn new(effect: Effect) -> Voice {
+        Voice {
+            tones: effect.tones(),
+            tone: 0,
+            position: 0,
+            oscillator: Oscillator::new(),
+        }
+    }
+
+    /**
+     * The next sample, `None` after the last tone.
+     */
+    fn next(&mut self) -> Option<i32> {
+        let tone = loop {
+            let tone = match self.tones.get(self.tone) {
+                Some(tone) => *tone,
+                None => return None,
+            };
+            if self.position < samples(tone.duration) {
+                break tone;
+            }
+            self.tone += 1;
+            self.position = 0;
+        };
+        let length = i64::from(samples(tone.duration));
+        let position = i64::from(self.position);
+        self.position += 1;
+
+        let (from, to) = (i64::from(tone.from), i64::from(tone.to));
+        let frequency = (from + (to - from) * position / length) as u32;
+        let amplitude = (i64::from(tone.volume) * (length - position) / length) as i32;
+        Some(self.oscillator.next(tone.waveform, frequency * 100, amplitude))
+    }
+}
+
+/**
+ * Number of samples of `duration` ms.
+ */
+pub fn samples(duration: u32) -> u32 {
+    duration * SAMPLE_RATE / 1000
+}
+
+/**
+ * Mixes the effects that are playing and the music.
+ */
+pub struct Mixer {
+    voices: [Option<Voice>; VOICES],
+    /// voice replaced when all are playing
+    oldest: usize,
+    music: Option<Sequencer>,
+    /// 256 is full volume
+    pub volume: i32,
+}
+
+impl Mixer {
+    pub fn new() -> Mixer {
+        Mixer {
+            voices: [None; VOICES],
+            oldest: 0,
+            music: None,
+            volume: 256,
+        }
+    }
+
+    /**
+     * Starts an effect, on top of those that are already playing.
+     */
+    pub fn play(&mut self, effect: Effect) {
+        let index = match self.voices.iter().position(|voice| voice.is_none()) {
+            Some(index) => index,
+            None => {
+                let index = self.oldest;
+                self.oldest = (self.oldest + 1) % VOICES;
+                index
+            }
+        };
+        self.voices[index] = Some(Voice::new(effect));
+    }
+
+    /**
+     * Plays a song in a loop, instead of the one that is playing, see
+     * `music` for the format. A song with mistakes is not played.
+     */
+    pub fn play_music(&mut self, song: &'static str) -> Result<(), SongError> {
+        self.music = Some(Sequencer::new(song)?);
+        Ok(())
+    }
+
+    /**
+     * Speed of the music in percent of the tempo of the song.
+     */
+    pub fn set_tempo(&mut self, percent: u32) {
+        if let Some(ref mut music) = self.music {
+            music.set_tempo(percent);
+        }
+    }
+
+    /**
+     * Fills `buffer` with interleaved stereo frames, left first. Both
+     * channels are the same, silence when nothing plays.
+     */
+    pub fn fill(&mut self, buffer: &mut [i16]) {
+        for frame in buffer.chunks_mut(2) {
+            let mut sum = match self.music {
+                Some(ref mut music) => music.next(),
+                None => 0,
+            };
+            for voice in self.voices.iter_mut() {
+                let sample = match *voice {
+                    Some(ref mut playing) => playing.next(),
+                    None => continue,
+                };
+                match sample {
+                    Some(sample) => sum += sample,
+                    None => *voice = None,
+                }
+            }
+            let mixed = (sum * self.volume) >> 8;
+            let sample = mixed.max(SAMPLE_MIN).min(SAMPLE_MAX) as i16;
+            for channel in frame.iter_mut() {
+                *channel = sample;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mixes `frames` stereo frames.
+    fn mix(mixer: &mut Mixer, frames: u32) -> Vec<i16> {
+        let mut buffer = vec![1; 2 * frames as usize];
+        mixer.fill(&mut buffer);
+        buffer
+    }
+
+    fn is_playing(mixer: &Mixer) -> bool {
+        mixer.voices.iter().any(|voice| voice.is_some())
+    }
+
+    #[test]
+    fn silence_is_all_zeros() {
+        let mut mixer = Mixer::new();
+        assert!(mix(&mut mixer, 100).iter().all(|&sample| sample == 0));
+    }
+
+    #[test]
+    fn channels_are_the_same() {
+        let mut mixer = Mixer::new();
+        mixer.play(Effect::Death);
+        let buffer = mix(&mut mixer, 1000);
+        assert!(buffer.chunks(2).all(|frame| frame[0] == frame[1]));
+    }
+
+    #[test]
+    fn click_ends_after_its_duration() {
+        let mut mixer = Mixer::new();
+        mixer.play(Effect::Click);
+        let frames = samples(10);
+        let buffer = mix(&mut mixer, frames);
+        assert!(buffer.iter().all(|&sample| sample != 0));
+        // the voice notices it is done with the next frame
+        assert!(is_playing(&mixer));
+        assert_eq!(mix(&mut mixer, 1), [0, 0]);
+        assert!(!is_playing(&mixer));
+    }
+
+    #[test]
+    fn clipping_saturates() {
+        let mut mixer = Mixer::new();
+        mixer.volume = 256 * 100;
+        mixer.play(Effect::Eat);
+        mixer.play(Effect::Eat);
+        let buffer = mix(&mut mixer, samples(20));
+        assert!(buffer.contains(&i16::MAX));
+        assert!(buffer.contains(&i16::MIN));
+    }
+
+    #[test]
+    fn fifth_effect_replaces_the_oldest() {
+        let mut mixer = Mixer::new();
+        for _ in 0..VOICES {
+            mixer.play(Effect::Death);
+        }
+        mixer.play(Effect::Click);
+        let tones = |voice: &Option<Voice>| voice.unwrap().tones.as_ptr();
+        assert_eq!(tones(&mixer.voices[0]), CLICK.as_ptr());
+        for voice in &mixer.voices[1..] {
+            assert_eq!(tones(voice), DEATH.as_ptr());
+        }
+        mixer.play(Effect::Turn);
+        assert_eq!(tones(&mixer.voices[1]), TURN.as_ptr());
+    }
+
+    #[test]
+    fn samples_of_a_duration() {
+        assert_eq!(samples(0), 0);
+        assert_eq!(samples(10), SAMPLE_RATE / 100);
+        assert_eq!(samples(1000), SAMPLE_RATE);
+    }
+}
@@ -0,0 +1,539 @@
+//! Background music: a small tracker-style sequencer.
+//!
+//! A song is text. Every row is a line with a note for each of the three
+//! channels (square, triangle and noise), rows are grouped into patterns and
+//! the order list plays the patterns in a loop:
+//!
+//! ```text
+//! # comments start with a hash
+//! row 125                 # ms per row at the normal game speed
+//! pattern intro
+//! A-4 A-2 C-7             # notes C-0 to B-8, sharps like C#4
+//! ... ... C-5             # ... lets a channel play on
+//! --- ... ...             # --- stops it
+//! pattern verse
+//! ...
+//! order intro verse verse
+//! ```
+//!
+//! On the noise channel the note sets how fast the noise changes, every hit
+//! fades out by itself. The text is read while playing, `check` finds
+//! mistakes in it first; `build.rs` checks every song and `host/songs.rs`
+//! renders it to a WAV file.
+
+use mixer::{self, Oscillator, Waveform};
+
+/// The game theme.
+pub const THEME: &str = include_str!("../assets/music/theme.song");
+
+/// Waveforms of the channels, in the order of the notes in a row.
+const CHANNELS: [Waveform; 3] = [Waveform::Square, Waveform::Triangle, Waveform::Noise];
+
+/// Peak amplitude of each channel, the effects are louder.
+const VOLUMES: [i32; 3] = [1800, 3000, 1500];
+
+/// Noise hits fade out within this many ms.
+const NOISE_DECAY: u32 = 120;
+
+/// The noise changes this many times as often as the note would oscillate.
+const NOISE_RATE: u32 = 16;
+
+/// Row duration in ms of songs without a `row` line.
+const DEFAULT_ROW_DURATION: u32 = 125;
+
+/// Longest row in ms, far below where `mixer::samples` would overflow.
+const MAX_ROW_DURATION: u32 = 10_000;
+
+/// C-4 to B-4 in 1/100 Hz.
+const OCTAVE_4: [u32; 12] = [
+    26163, 27718, 29366, 31113, 32963, 34923, 36999, 39200, 41530, 44000, 46616, 49388,
+];
+
+/**
+ * What a line of a song is.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Line<'a> {
+    /// blank or only a comment
+    Empty,
+    /// `row <ms>`
+    RowDuration(&'a str),
+    /// `pattern <name>`
+    Pattern(&'a str),
+    /// `order <names>`, the names
+    Order(&'a str),
+    /// the notes of a row
+    Row(&'a str),
+}
+
+fn parse_line(line: &str) -> Line {
+    let line = match line.find('#') {
+        Some(comment) => &line[..comment],
+        None => line,
+    }.trim();
+    let keyword = line.split_whitespace().next().unwrap_or("");
+    let rest = line[keyword.len()..].trim();
+    match keyword {
+        "" => Line::Empty,
+        "row" => Line::RowDuration(rest),
+        "pattern" => Line::Pattern(rest),
+        "order" => Line::Order(rest),
+        _ => Line::Row(line),
+    }
+}
+
+/**
+ * What a row does to a channel.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Note {
+    Hold,
+    Off,
+    /// frequency in 1/100 Hz
+    Play(u32),
+}
+
+fn parse_note(note: &str) -> Option<Note> {
+    match note {
+        "..." => return Some(Note::Hold),
+        "---" => return Some(Note::Off),
+        _ => {}
+    }
+    let bytes = note.as_bytes();
+    if bytes.len() != 3 {
+        return None;
+    }
+    let base = match bytes[0] {
+        b'C' => 0,
+        b'D' => 2,
+        b'E' => 4,
+        b'F' => 5,
+        b'G' => 7,
+        b'A' => 9,
+        b'B' => 11,
+        _ => return None,
+    };
+    let semitone = match bytes[1] {
+        b'-' => base,
+        b'#' if base != 4 && base != 11 => base + 1,
+        _ => return None,
+    };
+    let octave = match bytes[2] {
+        digit @ b'0'...b'8' => digit - b'0',
+        _ => return None,
+    };
+    Some(Note::Play(OCTAVE_4[semitone] << octave >> 4))
+}
+
+fn parse_row(row: &str) -> Option<[Note; 3]> {
+    let mut notes = [Note::Hold; 3];
+    let mut columns = row.split_whitespace();
+    for note in notes.iter_mut() {
+        *note = parse_note(columns.next()?)?;
+    }
+    match columns.next() {
+        Some(_) => None,
+        None => Some(notes),
+    }
+}
+
+/**
+ * The lines of a song from a byte offset on, each with the offset of the
+ * line after it.
+ */
+struct Lines<'a> {
+    song: &'a str,
+    offset: usize,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = (&'a str, usize);
+
+    fn next(&mut self) -> Option<(&'a str, usize)> {
+        let rest = &self.song[self.offset..];
+        if rest.is_empty() {
+            return None;
+        }
+        let length = rest.find('\n').map_or(rest.len(), |end| end + 1);
+        self.offset += length;
+        Some((&rest[..length], self.offset))
+    }
+}
+
+fn lines(song: &str, offset: usize) -> Lines {
+    Lines {
+        song,
+        offset,
+    }
+}
+
+/**
+ * The names in the order list.
+ */
+fn order(song: &str) -> &str {
+    lines(song, 0)
+        .filter_map(|(line, _)| match parse_line(line) {
+            Line::Order(names) => Some(names),
+            _ => None,
+        })
+        .next()
+        .unwrap_or("")
+}
+
+/**
+ * Offset of the line after `pattern <name>`.
+ */
+fn pattern_start(song: &str, name: &str) -> Option<usize> {
+    lines(song, 0)
+        .filter(|&(line, _)| parse_line(line) == Line::Pattern(name))
+        .map(|(_, next)| next)
+        .next()
+}
+
+/**
+ * A mistake in a song, `line` counts from 1.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SongError {
+    pub line: usize,
+    pub message: &'static str,
+}
+
+/**
+ * Finds the first mistake in a song: lines that are neither rows nor
+ * keywords, rows outside of patterns, a missing or empty order list and
+ * patterns in it that do not exist or have no rows.
+ */
+pub fn check(song: &str) -> Result<(), SongError> {
+    let mut order_line = None;
+    let mut in_pattern = false;
+    for (index, (line, _)) in lines(song, 0).enumerate() {
+        let error = |message| {
+            Err(SongError {
+                line: index + 1,
+                message,
+            })
+        };
+        match parse_line(line) {
+            Line::Empty => {}
+            Line::RowDuration(ms) => match ms.parse::<u32>() {
+                Ok(ms) if ms > 0 && ms <= MAX_ROW_DURATION => {}
+                _ => return error("`row` needs the duration of a row, 1 to 10000 ms"),
+            },
+            Line::Pattern(name) => {
+                if name.is_empty() || name.contains(char::is_whitespace) {
+                    return error("`pattern` needs a name without spaces");
+                }
+                in_pattern = true;
+            }
+            Line::Order(_) => {
+                if order_line.is_some() {
+                    return error("there can only be one `order`");
+                }
+                order_line = Some(index + 1);
+                in_pattern = false;
+            }
+            Line::Row(row) => {
+                if !in_pattern {
+                    return error("rows have to follow a `pattern` line");
+                }
+                if parse_row(row).is_none() {
+                    return error("a row needs three notes like C-4, C#4, ... or ---");
+                }
+            }
+        }
+    }
+    let line = match order_line {
+        Some(line) => line,
+        None => {
+            return Err(SongError {
+                line: lines(song, 0).count(),
+                message: "the song needs an `order`",
+            })
+        }
+    };
+    let error = |message| Err(SongError { line, message });
+    let names = order(song);
+    if names.is_empty() {
+        return error("the order is empty");
+    }
+    for name in names.split_whitespace() {
+        let start = match pattern_start(song, name) {
+            Some(start) => start,
+            None => return error("the order names a pattern that does not exist"),
+        };
+        let first = lines(song, start)
+            .map(|(line, _)| parse_line(line))
+            .find(|&line| line != Line::Empty);
+        match first {
+            Some(Line::Row(_)) => {}
+            _ => return error("the order names a pattern without rows"),
+        }
+    }
+    Ok(())
+}
+
+/**
+ * A channel of the sequencer, it plays a note until the next one.
+ */
+#[derive(Clone, Copy)]
+struct Channel {
+    waveform: Waveform,
+    volume: i32,
+    /// frequency in 1/100 Hz, 0 if silent
+    centihertz: u32,
+    /// samples since the note started
+    played: u32,
+    oscillator: Oscillator,
+}
+
+impl Channel {
+    fn new(waveform: Waveform, volume: i32) -> Channel {
+        Channel {
+            waveform,
+            volume,
+            centihertz: 0,
+            played: 0,
+            oscillator: Oscillator::new(),
+        }
+    }
+
+    fn start(&mut self, note: Note) {
+        match note {
+            Note::Hold => {}
+            Note::Off => self.centihertz = 0,
+            Note::Play(centihertz) => {
+                self.centihertz = centihertz;
+                self.played = 0;
+            }
+        }
+    }
+
+    fn next(&mut self) -> i32 {
+        if self.centihertz == 0 {
+            return 0;
+        }
+        let (frequency, amplitude) = match self.waveform {
+            Waveform::Noise => {
+                let decay = mixer::samples(NOISE_DECAY);
+                if self.played >= decay {
+                    self.centihertz = 0;
+                    return 0;
+                }
+                let left = i64::from(decay - self.played);
+                let amplitude = i64::from(self.volume) * left / i64::from(decay);
+                (self.centihertz * NOISE_RATE, amplitude as i32)
+            }
+            _ => (self.centihertz, self.volume),
+        };
+        self.played += 1;
+        self.oscillator.next(self.waveform, frequency, amplitude)
+    }
+}
+
+/**
+ * Plays a song in a loop, see the top of this file for the format.
+ */
+pub struct Sequencer {
+    song: &'static str,
+    /// ms per row at 100 % tempo
+    row_duration: u32,
+    /// in percent of the tempo of the song
+    tempo: u32,
+    /// patterns in the order list
+    order_length: usize,
+    /// index of the current pattern in the order list
+    order: usize,
+    /// offset of the next line to play
+    next_line: usize,
+    /// samples until the next row starts
+    samples_left: u32,
+    channels: [Channel; 3],
+}
+
+impl Sequencer {
+    pub fn new(song: &'static str) -> Result<Sequencer, SongError> {
+        check(song)?;
+        let row_duration = lines(song, 0)
+            .filter_map(|(line, _)| match parse_line(line) {
+                Line::RowDuration(ms) => ms.parse().ok(),
+                _ => None,
+            })
+            .next()
+            .unwrap_or(DEFAULT_ROW_DURATION);
+        let order_length = order(song).split_whitespace().count();
+        Ok(Sequencer {
+            song,
+            row_duration: row_duration.max(1),
+            tempo: 100,
+            order_length,
+            // the current pattern is over, the first row starts the first one
+            order: order_length.saturating_sub(1),
+            next_line: song.len(),
+            samples_left: 0,
+            channels: [
+                Channel::new(CHANNELS[0], VOLUMES[0]),
+                Channel::new(CHANNELS[1], VOLUMES[1]),
+                Channel::new(CHANNELS[2], VOLUMES[2]),
+            ],
+        })
+    }
+
+    pub fn set_tempo(&mut self, percent: u32) {
+        self.tempo = percent.max(1);
+    }
+
+    /**
+     * The next sample of all channels together.
+     */
+    pub fn next(&mut self) -> i32 {
+        if self.samples_left == 0 {
+            let notes = self.next_row().unwrap_or([Note::Off; 3]);
+            for (channel, &note) in self.channels.iter_mut().zip(notes.iter()) {
+                channel.start(note);
+            }
+            let samples = mixer::samples(self.row_duration) * 100 / self.tempo;
+            self.samples_left = samples.max(1);
+        }
+        self.samples_left -= 1;
+        self.channels.iter_mut().map(|channel| channel.next()).sum()
+    }
+
+    /**
+     * The notes of the next row, continuing with the next pattern of the
+     * order list at the end of a pattern. `None` if there is nothing to play.
+     */
+    fn next_row(&mut self) -> Option<[Note; 3]> {
+        if self.order_length == 0 {
+            return None;
+        }
+        for _ in 0..self.order_length + 1 {
+            for (line, next) in lines(self.song, self.next_line) {
+                self.next_line = next;
+                match parse_line(line) {
+                    Line::Empty => {}
+                    Line::Row(row) => if let Some(notes) = parse_row(row) {
+                        return Some(notes);
+                    },
+                    _ => break,
+                }
+            }
+            self.order = (self.order + 1) % self.order_length;
+            let name = order(self.song).split_whitespace().nth(self.order);
+            self.next_line = name.and_then(|name| pattern_start(self.song, name))
+                .unwrap_or(self.song.len());
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(song: &str) -> Option<(usize, &'static str)> {
+        check(song).err().map(|error| (error.line, error.message))
+    }
+
+    #[test]
+    fn notes() {
+        assert_eq!(parse_note("..."), Some(Note::Hold));
+        assert_eq!(parse_note("---"), Some(Note::Off));
+        assert_eq!(parse_note("A-4"), Some(Note::Play(44000)));
+        assert_eq!(parse_note("A-5"), Some(Note::Play(88000)));
+        assert_eq!(parse_note("C#4"), Some(Note::Play(27718)));
+        assert_eq!(parse_note("C-0"), Some(Note::Play(26163 >> 4)));
+        assert_eq!(parse_note("B-8"), Some(Note::Play(49388 << 4)));
+    }
+
+    #[test]
+    fn bad_notes() {
+        for note in &["", "A", "A-", "A-44", "H-4", "a-4", "A+4", "A-9", "A-x", "..", "----"] {
+            assert_eq!(parse_note(note), None, "{:?}", note);
+        }
+    }
+
+    #[test]
+    fn no_sharp_after_e_and_b() {
+        assert_eq!(parse_note("E#4"), None);
+        assert_eq!(parse_note("B#4"), None);
+        assert!(parse_note("F#4").is_some());
+        assert!(parse_note("A#4").is_some());
+    }
+
+    #[test]
+    fn rows_need_three_notes() {
+        assert!(parse_row("A-4 ... ---").is_some());
+        assert_eq!(parse_row("A-4 ..."), None);
+        assert_eq!(parse_row("A-4 ... --- C-4"), None);
+        assert_eq!(parse_row("A-4 E#4 ---"), None);
+    }
+
+    #[test]
+    fn good_song() {
+        let song = "# a song\nrow 100\npattern a\nA-4 ... ---\n\n\
+                    pattern b\n--- C-2 C-6\norder a b a\n";
+        assert_eq!(check(song), Ok(()));
+        assert_eq!(check(THEME), Ok(()));
+    }
+
+    #[test]
+    fn bad_row_is_found() {
+        let song = "pattern a\nA-4 ... ---\nE#4 ... ---\norder a\n";
+        assert_eq!(error(song).map(|(line, _)| line), Some(3));
+        assert_eq!(error("A-4 ... ---\norder a\n").map(|(line, _)| line), Some(1));
+    }
+
+    #[test]
+    fn order_is_needed() {
+        assert_eq!(
+            error("pattern a\nA-4 ... ---\n"),
+            Some((2, "the song needs an `order`"))
+        );
+        assert_eq!(
+            error("pattern a\nA-4 ... ---\norder   # nothing\n"),
+            Some((3, "the order is empty"))
+        );
+        assert_eq!(
+            error("order a\norder a\npattern a\nA-4 ... ---\n"),
+            Some((2, "there can only be one `order`"))
+        );
+    }
+
+    #[test]
+    fn order_names_existing_patterns_with_rows() {
+        assert_eq!(
+            error("pattern a\nA-4 ... ---\norder a b\n"),
+            Some((3, "the order names a pattern that does not exist"))
+        );
+        assert_eq!(
+            error("pattern a\npattern b\nA-4 ... ---\norder a b\n"),
+            Some((4, "the order names a pattern without rows"))
+        );
+    }
+
+    #[test]
+    fn row_durations() {
+        let song = |row: &str| format!("row {}\npattern a\nA-4 ... ---\norder a\n", row);
+        assert_eq!(check(&song("1")), Ok(()));
+        assert_eq!(check(&song("10000")), Ok(()));
+        for row in &["0", "-5", "fast", "", "10001", "300000", "4294967296"] {
+            assert_eq!(error(&song(row)).map(|(line, _)| line), Some(1), "{:?}", row);
+        }
+        // where `mixer::samples` would overflow
+        assert!(u32::max_value() / mixer::SAMPLE_RATE < 300_000);
+    }
+
+    #[test]
+    fn sequencer_plays_the_rows() {
+        let song = "row 10\npattern a\nA-4 ... ---\n--- ... ---\norder a\n";
+        let mut sequencer = Sequencer::new(song).unwrap();
+        let row = mixer::samples(10) as usize;
+        let first: Vec<i32> = (0..row).map(|_| sequencer.next()).collect();
+        let second: Vec<i32> = (0..row).map(|_| sequencer.next()).collect();
+        assert!(first.iter().any(|&sample| sample != 0));
+        assert!(second.iter().all(|&sample| sample == 0));
+        // the order loops
+        assert!((0..row).map(|_| sequencer.next()).any(|sample| sample != 0));
+    }
+}
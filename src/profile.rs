@@ -0,0 +1,84 @@
+//! The board the game runs on.
+//!
+//! The default profile is the STM32F746 Discovery board. Build with
+//! `--features f769-disco` for the 800x480 panel of the STM32F769 Discovery
+//! board. Everything that depends on the display size uses `WIDTH` and
+//! `HEIGHT` from here, layouts are computed relative to them, and so do the
+//! framebuffers.
+
+/**
+ * Maps the coordinates reported by the touch controller to screen pixels:
+ * `screen = touch * numerator / denominator`, after swapping the axes if the
+ * controller is mounted rotated.
+ */
+#[derive(Clone, Copy)]
+pub struct TouchMapping {
+    pub swap_axes: bool,
+    pub x_scale: (usize, usize),
+    pub y_scale: (usize, usize),
+}
+
+impl TouchMapping {
+    pub fn map(&self, (x, y): (u16, u16)) -> (u16, u16) {
+        let (x, y) = if self.swap_axes { (y, x) } else { (x, y) };
+        let scale = |value: u16, (num, den): (usize, usize)| (value as usize * num / den) as u16;
+        (scale(x, self.x_scale), scale(y, self.y_scale))
+    }
+}
+
+#[cfg(not(feature = "f769-disco"))]
+mod board {
+    use super::TouchMapping;
+    use stm32f7::lcd;
+
+    pub const WIDTH: usize = 480;
+    pub const HEIGHT: usize = 272;
+    /// the FT5336 reports screen pixels
+    pub const TOUCH: TouchMapping = TouchMapping {
+        swap_axes: false,
+        x_scale: (1, 1),
+        y_scale: (1, 1),
+    };
+    /// the game layer
+    pub type Layer1Format = lcd::FramebufferArgb8888;
+    pub const LAYER_1_BYTES_PER_PIXEL: usize = 4;
+    /// the overlay layer for menus and text
+    pub type Layer2Format = lcd::FramebufferAl88;
+    pub const LAYER_2_BYTES_PER_PIXEL: usize = 2;
+}
+
+#[cfg(feature = "f769-disco")]
+mod board {
+    use super::TouchMapping;
+    use stm32f7::lcd;
+
+    pub const WIDTH: usize = 800;
+    pub const HEIGHT: usize = 480;
+    /// the FT6206 of the panel reports screen pixels as well
+    pub const TOUCH: TouchMapping = TouchMapping {
+        swap_axes: false,
+        x_scale: (1, 1),
+        y_scale: (1, 1),
+    };
+    /// the game layer
+    pub type Layer1Format = lcd::FramebufferArgb8888;
+    pub const LAYER_1_BYTES_PER_PIXEL: usize = 4;
+    /// the overlay layer for menus and text
+    pub type Layer2Format = lcd::FramebufferAl88;
+    pub const LAYER_2_BYTES_PER_PIXEL: usize = 2;
+}
+
+pub use self::board::*;
+
+/// Start of the SDRAM, the lcd driver places both framebuffers at its
+/// beginning. `Graphics::new` checks the addresses and the row lengths
+/// against the ones the driver set up.
+const SDRAM_START: usize = 0xC000_0000;
+/// Start of the layer 1 framebuffer.
+pub const LAYER_1_START: usize = SDRAM_START;
+/// Bytes of the layer 1 framebuffer.
+pub const LAYER_1_SIZE: usize = WIDTH * HEIGHT * LAYER_1_BYTES_PER_PIXEL;
+/// Start of the layer 2 framebuffer, right behind the one of layer 1.
+pub const LAYER_2_START: usize = LAYER_1_START + LAYER_1_SIZE;
+/// Bytes of the layer 2 framebuffer.
+pub const LAYER_2_SIZE: usize = WIDTH * HEIGHT * LAYER_2_BYTES_PER_PIXEL;
@@ -0,0 +1,657 @@
+//! Draws the game and its screens on a `Canvas`.
+//!
+//! Nothing in here touches the hardware, `Game` decides what to draw and on
+//! which layer.
+
+use alloc::String;
+use animation::Frame;
+use assets;
+use canvas::{Blend, Canvas, Color};
+use font;
+use rules::{self, Rules};
+use theme::Theme;
+use sprite::{RotDirection, Sprite};
+use ui::{Button, ImageButton, Label, Menu, Rect, Style, Toggle, Widget};
+
+/// Cell size in pixels the sprites are drawn for, they are scaled for other sizes.
+pub const SPRITE_CELL_SIZE: usize = 10;
+
+/**
+ * Board presets, they differ in the size of a cell and so in the number of
+ * cells on the screen.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum BoardSize {
+    /// 8 pixel cells
+    Tiny,
+    /// 10 pixel cells, the size the sprites are drawn for
+    Normal,
+    /// 16 pixel cells
+    Big,
+}
+
+impl BoardSize {
+    pub fn cell_size(self) -> usize {
+        match self {
+            BoardSize::Tiny => 8,
+            BoardSize::Normal => SPRITE_CELL_SIZE,
+            BoardSize::Big => 16,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BoardSize::Tiny => "tiny board",
+            BoardSize::Normal => "normal board",
+            BoardSize::Big => "big board",
+        }
+    }
+
+    /**
+     * The preset after this one, for cycling through them in the menu.
+     */
+    pub fn next(self) -> BoardSize {
+        match self {
+            BoardSize::Tiny => BoardSize::Normal,
+            BoardSize::Normal => BoardSize::Big,
+            BoardSize::Big => BoardSize::Tiny,
+        }
+    }
+}
+
+/// Fractional bits of the movement progress used by `interpolate`.
+pub const PROGRESS_BITS: u32 = 8;
+/// A progress of one whole cell.
+pub const PROGRESS_ONE: u32 = 1 << PROGRESS_BITS;
+
+/// The apple shrinks a little now and then.
+pub static APPLE_PULSE: [Frame<&'static Sprite<u32>>; 2] = [
+    Frame {
+        value: &assets::APPLE,
+        duration: 700,
+    },
+    Frame {
+        value: &assets::APPLE_SMALL,
+        duration: 300,
+    },
+];
+
+/// The head chomps after eating an apple, `true` is an open mouth.
+pub static HEAD_CHOMP: [Frame<bool>; 4] = [
+    Frame {
+        value: true,
+        duration: 80,
+    },
+    Frame {
+        value: false,
+        duration: 80,
+    },
+    Frame {
+        value: true,
+        duration: 80,
+    },
+    Frame {
+        value: false,
+        duration: 80,
+    },
+];
+
+/// The snake on the welcome screen talks while the text is written.
+pub static WELCOME_MOUTH: [Frame<bool>; 2] = [
+    Frame {
+        value: true,
+        duration: 60,
+    },
+    Frame {
+        value: false,
+        duration: 60,
+    },
+];
+
+/**
+ * What occupies a cell of the grid.
+ */
+pub enum CellContent {
+    Empty,
+    /// the snake parts are drawn in the color of their player
+    Head {
+        player: usize,
+        rot: RotDirection,
+        mouth_open: bool,
+    },
+    /// a straight segment is horizontal before rotating, a corner connects
+    /// the left and the bottom side
+    Body {
+        player: usize,
+        corner: bool,
+        rot: RotDirection,
+    },
+    /// points to the left before rotating
+    Tail { player: usize, rot: RotDirection },
+    Apple { sprite: &'static Sprite<u32> },
+}
+
+/**
+ * Draws a frame around the field.
+ */
+pub fn draw_frame<C: Canvas>(canvas: &mut C, cell_size: usize, theme: &Theme) {
+    let (left, top) = (cell_size - 2, cell_size - 2);
+    let right = canvas.width() - cell_size;
+    let bottom = canvas.height() - cell_size - 1;
+    canvas.draw_rect(
+        left,
+        top,
+        right - left + 1,
+        bottom - top + 1,
+        theme.frame,
+    );
+}
+
+/**
+ * Clears a cell and draws its content.
+ */
+pub fn draw_cell<C: Canvas>(
+    canvas: &mut C,
+    cell: (usize, usize),
+    cell_size: usize,
+    content: CellContent,
+    theme: &Theme,
+) {
+    let x = cell.0 * cell_size;
+    let y = cell.1 * cell_size;
+    canvas.fill_rect(x, y, cell_size, cell_size, Color::transparent());
+    draw_content_at(canvas, x, y, cell_size, content, theme);
+}
+
+/**
+ * Draws the content of a cell with its top left corner at pixel x,y, without
+ * clearing anything first. Used for the head and tail in between two cells.
+ */
+pub fn draw_content_at<C: Canvas>(
+    canvas: &mut C,
+    x: usize,
+    y: usize,
+    cell_size: usize,
+    content: CellContent,
+    theme: &Theme,
+) {
+    let tint = |player| Blend::Tint(theme.player_color(player));
+    let scale = (cell_size, SPRITE_CELL_SIZE);
+    match content {
+        CellContent::Empty => {}
+        CellContent::Head {
+            player,
+            rot,
+            mouth_open,
+        } => {
+            let sprite = if mouth_open {
+                &assets::SNAKE_MOUTH_OPEN
+            } else {
+                &assets::SNAKE_MOUTH_CLOSED
+            };
+            canvas.blit_scaled(sprite, x, y, rot, tint(player), scale);
+        }
+        CellContent::Body {
+            player,
+            corner,
+            rot,
+        } => {
+            let sprite = if corner {
+                &assets::SNAKE_BODY_CORNER
+            } else {
+                &assets::SNAKE_BODY_STRAIGHT
+            };
+            canvas.blit_scaled(sprite, x, y, rot, tint(player), scale);
+        }
+        CellContent::Tail { player, rot } => {
+            canvas.blit_scaled(&assets::SNAKE_TAIL, x, y, rot, tint(player), scale)
+        }
+        CellContent::Apple { sprite } => {
+            canvas.blit_scaled(sprite, x, y, RotDirection::R0, Blend::SkipTransparent, scale)
+        }
+    }
+}
+
+/**
+ * Pixel position of something moving from cell `from` to the neighbouring
+ * cell `to`. `progress` is a fixed point fraction of the way, 0 to
+ * `PROGRESS_ONE`. If the cells are not neighbours (the snake wrapped around
+ * the edge) the position of `to` is returned.
+ */
+pub fn interpolate(
+    from: (usize, usize),
+    to: (usize, usize),
+    progress: u32,
+    cell_size: usize,
+) -> (usize, usize) {
+    let dx = to.0 as i32 - from.0 as i32;
+    let dy = to.1 as i32 - from.1 as i32;
+    if dx.abs() + dy.abs() != 1 {
+        return (to.0 * cell_size, to.1 * cell_size);
+    }
+    let progress = progress.min(PROGRESS_ONE) as i32;
+    let block = cell_size as i32;
+    let x = from.0 as i32 * block + ((dx * block * progress) >> PROGRESS_BITS);
+    let y = from.1 as i32 * block + ((dy * block * progress) >> PROGRESS_BITS);
+    (x as usize, y as usize)
+}
+
+/// Largest width of the minimap in pixels.
+pub const MINIMAP_MAX_WIDTH: usize = 96;
+/// Distance of the minimap from the screen edges.
+const MINIMAP_MARGIN: usize = 10;
+
+/**
+ * Where the minimap goes and how many cells share one of its pixels.
+ */
+pub struct Minimap {
+    pub x: usize,
+    pub y: usize,
+    /// size of the world in cells
+    pub world: (usize, usize),
+    pub cells_per_pixel: usize,
+}
+
+impl Minimap {
+    /**
+     * A minimap in the top right corner of a `screen_width` wide screen.
+     */
+    pub fn new(screen_width: usize, world: (usize, usize)) -> Minimap {
+        let cells_per_pixel = (world.0 + MINIMAP_MAX_WIDTH - 1) / MINIMAP_MAX_WIDTH;
+        let width = world.0 / cells_per_pixel;
+        Minimap {
+            x: screen_width - width - MINIMAP_MARGIN,
+            y: MINIMAP_MARGIN,
+            world,
+            cells_per_pixel,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.world.0 / self.cells_per_pixel
+    }
+
+    pub fn height(&self) -> usize {
+        self.world.1 / self.cells_per_pixel
+    }
+
+    fn point(&self, cell: (usize, usize)) -> (usize, usize) {
+        (
+            self.x + (cell.0 / self.cells_per_pixel).min(self.width() - 1),
+            self.y + (cell.1 / self.cells_per_pixel).min(self.height() - 1),
+        )
+    }
+}
+
+/**
+ * Draws an overview of the whole world, meant for layer 2: every snake as
+ * `(player, cells)`, the apples and the outline of the part on the screen,
+ * `view` given in cells as (x, y, width, height).
+ */
+pub fn draw_minimap<C: Canvas>(
+    canvas: &mut C,
+    minimap: &Minimap,
+    theme: &Theme,
+    snakes: &[(usize, &[(usize, usize)])],
+    apples: &[(usize, usize)],
+    view: (usize, usize, usize, usize),
+) {
+    let (width, height) = (minimap.width(), minimap.height());
+    canvas.fill_rect(minimap.x, minimap.y, width, height, Color::rgba(0, 0, 0, 160));
+    canvas.draw_rect(minimap.x, minimap.y, width, height, theme.frame);
+    let (left, top) = minimap.point((view.0, view.1));
+    let (right, bottom) = minimap.point((view.0 + view.2 - 1, view.1 + view.3 - 1));
+    canvas.draw_rect(left, top, right - left + 1, bottom - top + 1, theme.ui.pressed);
+    for &(player, cells) in snakes {
+        let color = theme.player_color(player);
+        for &cell in cells {
+            let (x, y) = minimap.point(cell);
+            canvas.set_pixel(x, y, color);
+        }
+    }
+    for &apple in apples {
+        let (x, y) = minimap.point(apple);
+        // a little bigger, so the apple is easy to spot
+        canvas.fill_rect(x.saturating_sub(1), y.saturating_sub(1), 3, 3, theme.ui.foreground);
+    }
+}
+
+/**
+ * What a touch on a menu widget does.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum MenuAction {
+    None,
+    Resume,
+    NewGame,
+    SmoothMovement,
+    Theme,
+    Arena,
+    BoardSize,
+    TableTop,
+    Players,
+    Goal,
+    Rounds,
+    NextRound,
+    Calibrate,
+}
+
+/**
+ * Text of the button that picks the number of players.
+ */
+pub fn players_name(players: usize) -> &'static str {
+    match players {
+        1 => "1 player",
+        2 => "2 players",
+        3 => "3 players",
+        _ => "4 players",
+    }
+}
+
+/// Scale of the score text, see `font::draw_text`.
+const HUD_SCALE: usize = 2;
+/// Distance of the score from the bottom of the screen.
+const HUD_MARGIN: usize = 4;
+
+/**
+ * Draws `text` centered at the bottom of `area`, meant for layer 2. The old
+ * text is erased first.
+ */
+pub fn draw_hud<C: Canvas>(canvas: &mut C, area: Rect, text: &str, color: Color) {
+    let height = font::text_height(HUD_SCALE);
+    let y = area.y + area.height - height - HUD_MARGIN;
+    canvas.fill_rect(area.x, y, area.width, height, Color::transparent());
+    let x = area.x + area.width.saturating_sub(font::text_width(text, HUD_SCALE)) / 2;
+    font::draw_text(canvas, x, y, text, color, HUD_SCALE);
+}
+
+/**
+ * Shows or erases the warning at the top of layer 2 that the touch screen
+ * does not answer, in the colors of `style`.
+ */
+pub fn draw_touch_warning<C: Canvas>(canvas: &mut C, shown: bool, style: Style) {
+    let text = "touch screen not responding";
+    let height = font::text_height(HUD_SCALE) + 2 * HUD_MARGIN;
+    let width = canvas.width();
+    if !shown {
+        canvas.fill_rect(0, 0, width, height, Color::transparent());
+        return;
+    }
+    // the game stays visible behind the bar
+    let background = Color {
+        alpha: 192,
+        ..style.background
+    };
+    canvas.fill_rect(0, 0, width, height, background);
+    let x = width.saturating_sub(font::text_width(text, HUD_SCALE)) / 2;
+    font::draw_text(canvas, x, HUD_MARGIN, text, style.foreground, HUD_SCALE);
+}
+
+/// Distance of the calibration crosses from the edges of the screen.
+const CALIBRATION_INSET: usize = 40;
+/// Half the length of a bar of a calibration cross.
+const CROSS_SIZE: usize = 12;
+
+/**
+ * Where the calibration screen shows its crosses on a screen of the given
+ * size: near the corners and in the middle.
+ */
+pub fn calibration_targets((width, height): (usize, usize)) -> [(u16, u16); 5] {
+    let (left, top) = (CALIBRATION_INSET as u16, CALIBRATION_INSET as u16);
+    let (right, bottom) = ((width - CALIBRATION_INSET) as u16, (height - CALIBRATION_INSET) as u16);
+    let middle = ((width / 2) as u16, (height / 2) as u16);
+    [(left, top), (right, top), (right, bottom), (left, bottom), middle]
+}
+
+/**
+ * Covers layer 2 with the calibration screen: a cross at `target` to tap and
+ * `text` below the middle.
+ */
+pub fn draw_calibration_screen<C: Canvas>(canvas: &mut C, target: Option<(u16, u16)>, text: &str) {
+    let (width, height) = (canvas.width(), canvas.height());
+    canvas.fill_rect(0, 0, width, height, Color::rgba(0, 0, 0, 224));
+    let white = Color::rgb(255, 255, 255);
+    if let Some((x, y)) = target {
+        let (x, y) = (x as usize, y as usize);
+        canvas.fill_rect(x - CROSS_SIZE, y - 1, 2 * CROSS_SIZE + 1, 3, white);
+        canvas.fill_rect(x - 1, y - CROSS_SIZE, 3, 2 * CROSS_SIZE + 1, white);
+    }
+    let x = width.saturating_sub(font::text_width(text, HUD_SCALE)) / 2;
+    let y = height / 2 + 2 * CROSS_SIZE;
+    font::draw_text(canvas, x, y, text, white, HUD_SCALE);
+}
+
+/// Size of the screen the menus and the welcome screen were laid out for.
+pub const LAYOUT_SIZE: (usize, usize) = (480, 272);
+
+/**
+ * Returns the top left corner of the `LAYOUT_SIZE` area centered on a screen
+ * of the given size, the menus place their widgets relative to it.
+ */
+pub fn layout_origin((width, height): (usize, usize)) -> (usize, usize) {
+    (
+        width.saturating_sub(LAYOUT_SIZE.0) / 2,
+        height.saturating_sub(LAYOUT_SIZE.1) / 2,
+    )
+}
+
+/**
+ * What the pause menu shows as chosen.
+ */
+#[derive(Clone, Copy)]
+pub struct Options<'a> {
+    pub smooth_movement: bool,
+    pub arena: bool,
+    pub table_top: bool,
+    pub players: usize,
+    pub rules: Rules,
+    pub board: BoardSize,
+    pub theme: &'a Theme,
+}
+
+/**
+ * The pause menu for a screen of the given size, meant for layer 2. The arena
+ * toggle, the board size and the number of players take effect with the next
+ * game, the rules with the next match.
+ */
+pub fn pause_menu(options: &Options, screen: (usize, usize)) -> Menu<MenuAction> {
+    let Options {
+        smooth_movement,
+        arena,
+        table_top,
+        players,
+        rules,
+        board,
+        theme,
+    } = *options;
+    let (x, y) = layout_origin(screen);
+    let style = theme.ui;
+    let mut menu = Menu::new();
+    menu.add(
+        MenuAction::None,
+        Widget::Label(Label::image(x + 90 + 8, y + 20, &assets::PAUSE_SCREEN_LEFT)),
+    );
+    menu.add(
+        MenuAction::None,
+        Widget::Label(Label::image(x + 90 + 100 + 102 + 8, y + 20, &assets::PAUSE_SCREEN_RIGHT)),
+    );
+    menu.add(
+        MenuAction::None,
+        Widget::Label(Label::image(x + 100 + 90 + 8, y + 10 + 45, &assets::PAUSE_SCREEN_PAUSE)),
+    );
+    menu.add(
+        MenuAction::Resume,
+        Widget::ImageButton(ImageButton::new(
+            x + 100 + 8 + 90,
+            y + 139 + 6,
+            &assets::PAUSE_SCREEN_RESUME,
+            style,
+        )),
+    );
+    menu.add(
+        MenuAction::NewGame,
+        Widget::ImageButton(ImageButton::new(
+            x + 100 + 8 + 78,
+            y + 192 + 6,
+            &assets::PAUSE_SCREEN_NEW_GAME,
+            style,
+        )),
+    );
+    menu.add(
+        MenuAction::SmoothMovement,
+        Widget::Toggle(Toggle::new(
+            x + 100 + 8 + 78,
+            y + 192 + 6 + 40,
+            "smooth",
+            smooth_movement,
+            style,
+        )),
+    );
+    menu.add(
+        MenuAction::BoardSize,
+        Widget::Button(Button::new(
+            Rect::new(x + 8, y + 139 + 6 + 13, 170, 30),
+            board.name(),
+            style,
+        )),
+    );
+    menu.add(
+        MenuAction::Theme,
+        Widget::Button(Button::new(Rect::new(x + 8, y + 192 + 6, 170, 30), theme.name, style)),
+    );
+    menu.add(
+        MenuAction::Arena,
+        Widget::Toggle(Toggle::new(x + 8, y + 192 + 6 + 40, "arena", arena, style)),
+    );
+    menu.add(
+        MenuAction::TableTop,
+        Widget::Toggle(Toggle::new(x + 300, y + 192 + 6 + 40, "table", table_top, style)),
+    );
+    menu.add(
+        MenuAction::Players,
+        Widget::Button(Button::new(
+            Rect::new(x + 320, y + 139 + 6, 150, 30),
+            players_name(players),
+            style,
+        )),
+    );
+    menu.add(
+        MenuAction::Goal,
+        Widget::Button(Button::new(
+            Rect::new(x + 320, y + 139 + 6 + 40, 150, 30),
+            &rules.goal.name(),
+            style,
+        )),
+    );
+    menu.add(
+        MenuAction::Rounds,
+        Widget::Button(Button::new(
+            Rect::new(x + 8, y + 139 + 6 - 21, 170, 30),
+            &rules::rounds_name(rules.rounds),
+            style,
+        )),
+    );
+    menu.add(
+        MenuAction::Calibrate,
+        Widget::Button(Button::new(Rect::new(x + 8, y + 84, 84, 30), "touch", style)),
+    );
+    menu
+}
+
+/**
+ * The results after a round or a match for a screen of the given size, meant
+ * for layer 2: `title`, one line per player and a button that triggers
+ * `action`.
+ */
+pub fn scoreboard_menu(
+    theme: &Theme,
+    screen: (usize, usize),
+    title: &str,
+    lines: &[(String, Color)],
+    button: &'static str,
+    action: MenuAction,
+) -> Menu<MenuAction> {
+    let (x, y) = layout_origin(screen);
+    let centered = |mut label: Label| {
+        label.bounds.x = x + LAYOUT_SIZE.0.saturating_sub(label.bounds.width) / 2;
+        Widget::Label(label)
+    };
+    let mut menu = Menu::new();
+    menu.add(
+        MenuAction::None,
+        centered(Label::text(0, y + 30, title, theme.ui.foreground)),
+    );
+    for (i, &(ref line, color)) in lines.iter().enumerate() {
+        menu.add(
+            MenuAction::None,
+            centered(Label::text(0, y + 80 + 30 * i, line, color)),
+        );
+    }
+    menu.add(
+        action,
+        Widget::Button(Button::new(Rect::new(x + 165, y + 210, 150, 30), button, theme.ui)),
+    );
+    menu
+}
+
+/**
+ * The game over menu for a screen of the given size, meant for layer 2.
+ */
+pub fn restart_menu(theme: &Theme, screen: (usize, usize)) -> Menu<MenuAction> {
+    let (x, y) = layout_origin(screen);
+    let style = theme.ui;
+    let mut menu = Menu::new();
+    menu.add(
+        MenuAction::None,
+        Widget::Label(Label::image(x + 60 + 8, y + 20, &assets::PAUSE_SCREEN_LEFT)),
+    );
+    menu.add(
+        MenuAction::None,
+        Widget::Label(Label::image(
+            x + 90 + 100 + 20 + 102 + 8,
+            y + 20,
+            &assets::PAUSE_SCREEN_RIGHT,
+        )),
+    );
+    menu.add(
+        MenuAction::None,
+        Widget::Label(Label::image(x + 60 + 90 + 8, y + 10 + 45, &assets::PAUSE_SCREEN_GAME_OVER)),
+    );
+    menu.add(
+        MenuAction::NewGame,
+        Widget::ImageButton(ImageButton::new(
+            x + 100 + 8 + 78,
+            y + 192 + 6,
+            &assets::PAUSE_SCREEN_NEW_GAME,
+            style,
+        )),
+    );
+    menu
+}
+
+/**
+ * Draws the welcome screen.
+ */
+pub fn draw_welcome_screen<C: Canvas>(canvas: &mut C) {
+    let (x, y) = layout_origin((canvas.width(), canvas.height()));
+    canvas.blit(
+        &assets::WELCOME_SCREEN_BASE,
+        x,
+        y,
+        RotDirection::R0,
+        Blend::SkipTransparent,
+    );
+}
+
+/**
+ * Opens or shuts the mouth of the snake on the welcome screen.
+ */
+pub fn draw_welcome_mouth<C: Canvas>(canvas: &mut C, open: bool) {
+    let sprite = if open {
+        &assets::WELCOME_SCREEN_OPEN_MOUTH
+    } else {
+        &assets::WELCOME_SCREEN_CLOSED_MOUTH
+    };
+    let (x, y) = layout_origin((canvas.width(), canvas.height()));
+    canvas.blit(sprite, x + 188, y + 85, RotDirection::R0, Blend::Opaque);
+}
@@ -0,0 +1,261 @@
+//! Run-length encoding used for the sprites in flash.
+//!
+//! The data is a sequence of packets, each starting with a header byte:
+//!
+//! * `0x00 | (n - 1)`: the next `n` pixels are stored as they are
+//! * `0x80 | (n - 1)`: the next pixel is repeated `n` times
+//! * `0xc0 | (n - 1)`: `n` transparent pixels, no pixel data follows
+//!
+//! so a packet covers 1 to 64 pixels. Pixels are stored little endian with
+//! `Pixel::BYTES` bytes each; ARGB8888 pixels leave out the alpha byte since
+//! every stored pixel is opaque. The encoder is only built on the host:
+//! `build.rs` includes this file to compress the assets and to check that
+//! every one decodes to its original pixels.
+
+use core::marker::PhantomData;
+
+/// Header bits of a literal packet.
+pub const LITERAL: u8 = 0x00;
+/// Header bits of a run packet.
+pub const RUN: u8 = 0x80;
+/// Header bits of a transparent run packet.
+pub const TRANSPARENT_RUN: u8 = 0xc0;
+/// Mask for the packet kind in the header byte.
+pub const KIND_MASK: u8 = 0xc0;
+/// Maximum number of pixels covered by one packet.
+pub const MAX_PACKET_LENGTH: usize = 64;
+
+/**
+ * A framebuffer pixel that can be read from compressed data.
+ */
+pub trait Pixel: Copy {
+    /// number of bytes a stored pixel takes
+    const BYTES: usize;
+    /// value of the pixels in a transparent run
+    const TRANSPARENT: Self;
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+}
+
+impl Pixel for u32 {
+    const BYTES: usize = 3;
+    const TRANSPARENT: u32 = 0x00ff_ffff;
+    fn from_le_bytes(bytes: &[u8]) -> u32 {
+        0xff00_0000 | u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16
+    }
+}
+
+impl Pixel for u16 {
+    const BYTES: usize = 2;
+    const TRANSPARENT: u16 = 0x0000;
+    fn from_le_bytes(bytes: &[u8]) -> u16 {
+        u16::from(bytes[0]) | u16::from(bytes[1]) << 8
+    }
+}
+
+/**
+ * Streams the pixels out of compressed data without buffering them.
+ */
+pub struct Decoder<'a, P: Pixel> {
+    data: &'a [u8],
+    position: usize,
+    remaining: usize,
+    kind: u8,
+    pixel: PhantomData<P>,
+}
+
+impl<'a, P: Pixel> Decoder<'a, P> {
+    pub fn new(data: &'a [u8]) -> Decoder<'a, P> {
+        Decoder {
+            data,
+            position: 0,
+            remaining: 0,
+            kind: LITERAL,
+            pixel: PhantomData,
+        }
+    }
+}
+
+impl<'a, P: Pixel> Iterator for Decoder<'a, P> {
+    type Item = P;
+
+    fn next(&mut self) -> Option<P> {
+        if self.remaining == 0 {
+            let header = *self.data.get(self.position)?;
+            self.position += 1;
+            self.kind = header & KIND_MASK;
+            self.remaining = (header & !KIND_MASK) as usize + 1;
+        }
+        if self.kind != LITERAL && self.kind != RUN && self.kind != TRANSPARENT_RUN {
+            // corrupt data, stop instead of guessing
+            self.position = self.data.len();
+            self.remaining = 0;
+            return None;
+        }
+        self.remaining -= 1;
+        if self.kind == TRANSPARENT_RUN {
+            return Some(P::TRANSPARENT);
+        }
+        // truncated data ends with the last complete pixel
+        let bytes = self.data.get(self.position..self.position + P::BYTES)?;
+        let pixel = P::from_le_bytes(bytes);
+        // a run keeps pointing at its pixel until the last repetition
+        if self.kind == LITERAL || self.remaining == 0 {
+            self.position += P::BYTES;
+        }
+        Some(pixel)
+    }
+}
+
+/**
+ * Run-length encodes `pixels`, each stored as `bytes` little endian bytes.
+ * Pixels equal to `transparent` are not stored at all.
+ */
+#[cfg(not(target_os = "none"))]
+pub fn encode(pixels: &[u32], bytes: usize, transparent: u32) -> ::std::vec::Vec<u8> {
+    fn push_pixel(out: &mut ::std::vec::Vec<u8>, pixel: u32, bytes: usize) {
+        for i in 0..bytes {
+            out.push((pixel >> (8 * i)) as u8);
+        }
+    }
+    let starts_packet = |i: usize| {
+        pixels[i] == transparent || (i + 1 < pixels.len() && pixels[i] == pixels[i + 1])
+    };
+
+    let mut out = ::std::vec::Vec::new();
+    let mut i = 0;
+    while i < pixels.len() {
+        let mut run = 1;
+        while i + run < pixels.len() && run < MAX_PACKET_LENGTH && pixels[i + run] == pixels[i] {
+            run += 1;
+        }
+        if pixels[i] == transparent {
+            out.push(TRANSPARENT_RUN | (run - 1) as u8);
+            i += run;
+            continue;
+        }
+        if run > 1 {
+            out.push(RUN | (run - 1) as u8);
+            push_pixel(&mut out, pixels[i], bytes);
+            i += run;
+            continue;
+        }
+        // collect literals until the next run starts
+        let mut literal = 1;
+        while i + literal < pixels.len() && literal < MAX_PACKET_LENGTH
+            && !starts_packet(i + literal)
+        {
+            literal += 1;
+        }
+        out.push(LITERAL | (literal - 1) as u8);
+        for &pixel in &pixels[i..i + literal] {
+            push_pixel(&mut out, pixel, bytes);
+        }
+        i += literal;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_u16(pixels: &[u16]) -> Vec<u8> {
+        let pixels: Vec<u32> = pixels.iter().map(|&pixel| u32::from(pixel)).collect();
+        encode(&pixels, <u16 as Pixel>::BYTES, u32::from(<u16 as Pixel>::TRANSPARENT))
+    }
+
+    fn decode_u16(data: &[u8]) -> Vec<u16> {
+        Decoder::<u16>::new(data).collect()
+    }
+
+    /// Encodes and decodes `pixels`, returns the encoded data.
+    fn round_trip(pixels: &[u16]) -> Vec<u8> {
+        let data = encode_u16(pixels);
+        assert_eq!(decode_u16(&data), pixels);
+        data
+    }
+
+    #[test]
+    fn empty() {
+        assert!(round_trip(&[]).is_empty());
+        assert!(decode_u16(&[]).is_empty());
+    }
+
+    #[test]
+    fn single_pixel() {
+        assert_eq!(round_trip(&[0x1234]), [LITERAL, 0x34, 0x12]);
+        assert_eq!(round_trip(&[0]), [TRANSPARENT_RUN]);
+    }
+
+    #[test]
+    fn run_of_the_packet_limit() {
+        let data = round_trip(&[7; MAX_PACKET_LENGTH]);
+        assert_eq!(data, [RUN | (MAX_PACKET_LENGTH - 1) as u8, 7, 0]);
+    }
+
+    #[test]
+    fn run_one_over_the_packet_limit() {
+        let data = round_trip(&[7; MAX_PACKET_LENGTH + 1]);
+        assert_eq!(data, [RUN | (MAX_PACKET_LENGTH - 1) as u8, 7, 0, LITERAL, 7, 0]);
+        let data = round_trip(&[0; MAX_PACKET_LENGTH + 1]);
+        assert_eq!(data, [TRANSPARENT_RUN | (MAX_PACKET_LENGTH - 1) as u8, TRANSPARENT_RUN]);
+    }
+
+    #[test]
+    fn literals_up_to_the_packet_limit() {
+        let pixels: Vec<u16> = (1..MAX_PACKET_LENGTH as u16 + 2).collect();
+        let data = round_trip(&pixels);
+        assert_eq!(data[0], LITERAL | (MAX_PACKET_LENGTH - 1) as u8);
+        assert_eq!(data[1 + 2 * MAX_PACKET_LENGTH], LITERAL);
+    }
+
+    #[test]
+    fn literal_then_run() {
+        let data = round_trip(&[1, 2, 3, 3, 3]);
+        assert_eq!(data, [LITERAL | 1, 1, 0, 2, 0, RUN | 2, 3, 0]);
+    }
+
+    #[test]
+    fn run_then_literal() {
+        let data = round_trip(&[3, 3, 3, 1, 2]);
+        assert_eq!(data, [RUN | 2, 3, 0, LITERAL | 1, 1, 0, 2, 0]);
+    }
+
+    #[test]
+    fn transparent_between_literals() {
+        let data = round_trip(&[1, 0, 0, 2]);
+        assert_eq!(data, [LITERAL, 1, 0, TRANSPARENT_RUN | 1, LITERAL, 2, 0]);
+    }
+
+    #[test]
+    fn opaque_argb8888() {
+        let pixels = [0xff12_3456, 0xff12_3456, 0xffab_cdef];
+        let data = encode(&pixels, <u32 as Pixel>::BYTES, <u32 as Pixel>::TRANSPARENT);
+        let decoded: Vec<u32> = Decoder::<u32>::new(&data).collect();
+        assert_eq!(decoded, pixels);
+    }
+
+    #[test]
+    fn truncated_data_ends_early() {
+        let pixels = [1, 2, 3, 3, 3, 0, 0, 4];
+        let data = encode_u16(&pixels);
+        for end in 0..data.len() {
+            let decoded = decode_u16(&data[..end]);
+            assert!(decoded.len() <= pixels.len());
+            assert_eq!(decoded[..], pixels[..decoded.len()]);
+        }
+        // a header without its pixel
+        assert!(decode_u16(&[RUN | 5]).is_empty());
+        assert_eq!(decode_u16(&[LITERAL | 2, 1, 0, 2]), [1]);
+    }
+
+    #[test]
+    fn corrupt_data_does_not_panic() {
+        // 0x40 is not a packet kind
+        assert_eq!(decode_u16(&[LITERAL, 1, 0, 0x40, 2, 0]), [1]);
+        for header in 0..=255u8 {
+            let data = [header, 0xff, 0xee, header, 0x01];
+            assert!(decode_u16(&data).len() <= data.len() * MAX_PACKET_LENGTH);
+        }
+    }
+}
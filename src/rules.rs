@@ -0,0 +1,341 @@
+//! Match rules: when a round is over, who won it and who wins the match.
+//!
+//! Nothing in here knows about the board, `Game` reports the state of the
+//! round and shows the results.
+
+use alloc::{String, Vec};
+
+/**
+ * What the players play for in a round.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Goal {
+    /// the round goes on until every snake is dead
+    Endless,
+    /// the first to eat this many apples wins
+    Apples(usize),
+    /// the highest score when the time in ms is up wins
+    TimeLimit(usize),
+    /// the last snake alive wins
+    LastSurvivor,
+}
+
+/// Apples to win a round that can be picked in the pause menu.
+pub static APPLES: [usize; 3] = [5, 10, 20];
+
+/// Round lengths in ms that can be picked in the pause menu.
+pub static TIME_LIMITS: [usize; 3] = [60_000, 120_000, 300_000];
+
+/// Match lengths that can be picked in the pause menu, best of that many rounds.
+pub static ROUNDS: [usize; 3] = [1, 3, 5];
+
+impl Goal {
+    /**
+     * Text of the button that picks the goal.
+     */
+    pub fn name(self) -> String {
+        match self {
+            Goal::Endless => String::from("endless"),
+            Goal::Apples(apples) => format!("{} apples", apples),
+            Goal::TimeLimit(duration) if duration % 60_000 == 0 => match duration / 60_000 {
+                1 => String::from("1 minute"),
+                minutes => format!("{} minutes", minutes),
+            },
+            Goal::TimeLimit(duration) => format!("{} seconds", duration / 1000),
+            Goal::LastSurvivor => String::from("last alive"),
+        }
+    }
+
+    /**
+     * The goal after this one in the pause menu: endless, the apples in
+     * `APPLES`, the time limits in `TIME_LIMITS`, last alive and endless
+     * again.
+     */
+    pub fn next(self) -> Goal {
+        match self {
+            Goal::Endless => Goal::Apples(APPLES[0]),
+            Goal::Apples(apples) => match next_value(&APPLES, apples) {
+                Some(apples) => Goal::Apples(apples),
+                None => Goal::TimeLimit(TIME_LIMITS[0]),
+            },
+            Goal::TimeLimit(duration) => match next_value(&TIME_LIMITS, duration) {
+                Some(duration) => Goal::TimeLimit(duration),
+                None => Goal::LastSurvivor,
+            },
+            Goal::LastSurvivor => Goal::Endless,
+        }
+    }
+}
+
+/**
+ * The value after `value` in `values`, `None` after the last one or if it is
+ * not in there.
+ */
+fn next_value(values: &[usize], value: usize) -> Option<usize> {
+    let index = values.iter().position(|&v| v == value)?;
+    values.get(index + 1).cloned()
+}
+
+/**
+ * Text of the button that picks the number of rounds.
+ */
+pub fn rounds_name(rounds: usize) -> String {
+    match rounds {
+        1 => String::from("1 round"),
+        _ => format!("best of {}", rounds),
+    }
+}
+
+/**
+ * The number of rounds after `rounds` in `ROUNDS`.
+ */
+pub fn next_rounds(rounds: usize) -> usize {
+    let index = ROUNDS.iter().position(|&r| r == rounds).unwrap_or(0);
+    ROUNDS[(index + 1) % ROUNDS.len()]
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rules {
+    pub goal: Goal,
+    /// the match is best of this many rounds
+    pub rounds: usize,
+}
+
+/**
+ * How a round or a match ended.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome {
+    Winner(usize),
+    Draw,
+}
+
+impl Rules {
+    /**
+     * Whether the round is over, given the score and whether the snake of
+     * every player is alive and the time since the round started in ms.
+     * A round is always over once every snake is dead.
+     */
+    pub fn round_outcome(
+        &self,
+        scores: &[usize],
+        alive: &[bool],
+        elapsed: usize,
+    ) -> Option<Outcome> {
+        let alive_count = alive.iter().filter(|&&alive| alive).count();
+        let over = alive_count == 0 || match self.goal {
+            Goal::Endless => false,
+            Goal::Apples(apples) => scores.iter().any(|&score| score >= apples),
+            Goal::TimeLimit(duration) => elapsed >= duration,
+            Goal::LastSurvivor => alive.len() > 1 && alive_count == 1,
+        };
+        if !over {
+            return None;
+        }
+        if self.goal == Goal::LastSurvivor && alive_count == 1 {
+            return alive.iter().position(|&alive| alive).map(Outcome::Winner);
+        }
+        Some(best(scores))
+    }
+}
+
+/**
+ * The player with the highest value, a draw if several share it.
+ */
+fn best(values: &[usize]) -> Outcome {
+    let max = match values.iter().max() {
+        Some(&max) => max,
+        None => return Outcome::Draw,
+    };
+    let mut best = values
+        .iter()
+        .enumerate()
+        .filter(|&(_, &value)| value == max);
+    match (best.next(), best.next()) {
+        (Some((player, _)), None) => Outcome::Winner(player),
+        _ => Outcome::Draw,
+    }
+}
+
+/**
+ * Rounds won by every player so far.
+ */
+pub struct Match {
+    pub rules: Rules,
+    pub wins: Vec<usize>,
+    /// rounds played so far
+    pub round: usize,
+}
+
+impl Match {
+    pub fn new(rules: Rules, players: usize) -> Match {
+        Match {
+            rules,
+            wins: vec![0; players],
+            round: 0,
+        }
+    }
+
+    /**
+     * Counts a finished round.
+     */
+    pub fn finish_round(&mut self, outcome: Outcome) {
+        self.round += 1;
+        if let Outcome::Winner(player) = outcome {
+            self.wins[player] += 1;
+        }
+    }
+
+    /**
+     * Somebody won more than half of the rounds or all of them are played.
+     */
+    pub fn is_over(&self) -> bool {
+        let majority = self.rules.rounds / 2 + 1;
+        self.round >= self.rules.rounds || self.wins.iter().any(|&wins| wins >= majority)
+    }
+
+    /**
+     * The player with the most rounds won.
+     */
+    pub fn outcome(&self) -> Outcome {
+        best(&self.wins)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(goal: Goal, rounds: usize) -> Rules {
+        Rules {
+            goal,
+            rounds,
+        }
+    }
+
+    #[test]
+    fn names_show_the_values() {
+        assert_eq!(Goal::Endless.name(), "endless");
+        assert_eq!(Goal::Apples(5).name(), "5 apples");
+        assert_eq!(Goal::Apples(20).name(), "20 apples");
+        assert_eq!(Goal::TimeLimit(60_000).name(), "1 minute");
+        assert_eq!(Goal::TimeLimit(300_000).name(), "5 minutes");
+        assert_eq!(Goal::TimeLimit(90_000).name(), "90 seconds");
+        assert_eq!(Goal::LastSurvivor.name(), "last alive");
+        assert_eq!(rounds_name(1), "1 round");
+        assert_eq!(rounds_name(5), "best of 5");
+    }
+
+    #[test]
+    fn next_goal_goes_through_all_values() {
+        let mut goals = vec![Goal::Endless];
+        goals.extend(APPLES.iter().map(|&apples| Goal::Apples(apples)));
+        goals.extend(TIME_LIMITS.iter().map(|&duration| Goal::TimeLimit(duration)));
+        goals.push(Goal::LastSurvivor);
+        goals.push(Goal::Endless);
+        for pair in goals.windows(2) {
+            assert_eq!(pair[0].next(), pair[1]);
+        }
+        // a value that can not be picked goes on with the next goal
+        assert_eq!(Goal::Apples(7).next(), Goal::TimeLimit(TIME_LIMITS[0]));
+    }
+
+    #[test]
+    fn next_rounds_cycles() {
+        assert_eq!(next_rounds(1), 3);
+        assert_eq!(next_rounds(3), 5);
+        assert_eq!(next_rounds(5), 1);
+    }
+
+    #[test]
+    fn round_is_over_when_every_snake_is_dead() {
+        let rules = rules(Goal::Endless, 1);
+        assert_eq!(rules.round_outcome(&[3, 1], &[true, false], 0), None);
+        assert_eq!(
+            rules.round_outcome(&[3, 1], &[false, false], 0),
+            Some(Outcome::Winner(0))
+        );
+    }
+
+    #[test]
+    fn tie_is_a_draw() {
+        let rules = rules(Goal::Endless, 1);
+        assert_eq!(
+            rules.round_outcome(&[2, 2, 1], &[false, false, false], 0),
+            Some(Outcome::Draw)
+        );
+        assert_eq!(rules.round_outcome(&[], &[], 0), Some(Outcome::Draw));
+    }
+
+    #[test]
+    fn apples_goal() {
+        for &apples in APPLES.iter() {
+            let rules = rules(Goal::Apples(apples), 1);
+            let alive = [true, true];
+            assert_eq!(rules.round_outcome(&[apples - 1, 0], &alive, 0), None);
+            assert_eq!(
+                rules.round_outcome(&[1, apples], &alive, 0),
+                Some(Outcome::Winner(1))
+            );
+        }
+    }
+
+    #[test]
+    fn time_limit() {
+        for &duration in TIME_LIMITS.iter() {
+            let rules = rules(Goal::TimeLimit(duration), 1);
+            let alive = [true, true];
+            assert_eq!(rules.round_outcome(&[5, 2], &alive, duration - 1), None);
+            assert_eq!(
+                rules.round_outcome(&[5, 2], &alive, duration),
+                Some(Outcome::Winner(0))
+            );
+            assert_eq!(
+                rules.round_outcome(&[4, 4], &alive, duration),
+                Some(Outcome::Draw)
+            );
+        }
+    }
+
+    #[test]
+    fn last_survivor_wins_whatever_the_score() {
+        let rules = rules(Goal::LastSurvivor, 1);
+        assert_eq!(rules.round_outcome(&[0, 9, 3], &[true, true, false], 0), None);
+        assert_eq!(
+            rules.round_outcome(&[0, 9, 3], &[true, false, false], 0),
+            Some(Outcome::Winner(0))
+        );
+        // alone there is nobody to survive
+        assert_eq!(rules.round_outcome(&[0], &[true], 0), None);
+    }
+
+    #[test]
+    fn best_of_3_ends_after_2_wins() {
+        let mut game = Match::new(rules(Goal::Endless, 3), 2);
+        game.finish_round(Outcome::Winner(1));
+        assert!(!game.is_over());
+        game.finish_round(Outcome::Winner(1));
+        assert!(game.is_over());
+        assert_eq!(game.outcome(), Outcome::Winner(1));
+    }
+
+    #[test]
+    fn match_ends_after_all_rounds() {
+        let mut game = Match::new(rules(Goal::Endless, 3), 2);
+        game.finish_round(Outcome::Winner(0));
+        game.finish_round(Outcome::Draw);
+        assert!(!game.is_over());
+        game.finish_round(Outcome::Winner(1));
+        assert!(game.is_over());
+        assert_eq!(game.outcome(), Outcome::Draw);
+    }
+
+    #[test]
+    fn single_round_match() {
+        let mut game = Match::new(rules(Goal::Endless, 1), 3);
+        assert!(!game.is_over());
+        game.finish_round(Outcome::Draw);
+        assert!(game.is_over());
+        assert_eq!(game.outcome(), Outcome::Draw);
+    }
+}
@@ -0,0 +1,118 @@
+//! Sleeping between the things the game has to do.
+//!
+//! Instead of spinning until the next tick, the CPU waits with WFI for the
+//! next interrupt: the SysTick interrupt that counts `system_clock::ticks`
+//! every ms, or the touch controller. Its interrupt line (PI13) is routed to
+//! EXTI line 13, so the touch screen only has to be read over I2C after it
+//! signalled a touch.
+
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use stm32f7::{i2c, system_clock};
+
+/// Time between two frames in ms, the refresh rate of the display.
+pub const FRAME_DURATION: usize = 16;
+
+/// 7 bit I2C address of the FT5336 touch controller.
+const FT5336_ADDRESS: u8 = 0x38;
+/// Interrupt mode register of the touch controller.
+const FT5336_G_MODE: u8 = 0xa4;
+/// Pulse the interrupt line for every touch report, instead of holding it.
+const G_MODE_TRIGGER: u8 = 0x01;
+
+/// RCC APB2 peripheral clock enable register.
+const RCC_APB2ENR: usize = 0x4002_3844;
+/// Clock of the system configuration controller.
+const APB2ENR_SYSCFGEN: u32 = 1 << 14;
+/// External interrupt configuration register 4 (lines 12 to 15).
+const SYSCFG_EXTICR4: usize = 0x4001_3800 + 0x14;
+/// Bits of line 13 in EXTICR4 and the value for port I.
+const EXTICR4_LINE_13_SHIFT: u32 = 4;
+const EXTICR_PORT_I: u32 = 8;
+
+/// Base address of the EXTI registers.
+const EXTI_START: usize = 0x4001_3c00;
+/// Interrupt mask register.
+const EXTI_IMR: usize = EXTI_START;
+/// Falling trigger selection register.
+const EXTI_FTSR: usize = EXTI_START + 0x0c;
+/// Pending register, a bit is cleared by writing 1.
+const EXTI_PR: usize = EXTI_START + 0x14;
+/// The touch controller pulls PI13 low on a touch.
+const EXTI_LINE_TOUCH: u32 = 1 << 13;
+
+
+/// The touch interrupt is set up, otherwise the controller has to be polled.
+static TOUCH_INTERRUPT: AtomicBool = AtomicBool::new(false);
+/// The touch controller signalled since the touch screen was last read.
+static TOUCH_SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+/**
+ * Lets the touch controller pulse its interrupt line on every touch report
+ * and routes the line to EXTI line 13. `on_touch_interrupt` has to be
+ * registered for the EXTI15_10 interrupt afterwards.
+ */
+pub fn init_touch_interrupt(i2c_3: &mut i2c::I2C) -> Result<(), i2c::Error> {
+    i2c_3.connect::<u8, _>(i2c::Address::bits_7(FT5336_ADDRESS), |mut conn| {
+        conn.write(FT5336_G_MODE, G_MODE_TRIGGER)
+    })?;
+    unsafe {
+        let apb2enr = ptr::read_volatile(RCC_APB2ENR as *const u32);
+        ptr::write_volatile(RCC_APB2ENR as *mut u32, apb2enr | APB2ENR_SYSCFGEN);
+
+        // PI13 is an input after reset, it only has to be connected to line 13
+        let exticr4 = ptr::read_volatile(SYSCFG_EXTICR4 as *const u32);
+        let line_13 = 0b1111 << EXTICR4_LINE_13_SHIFT;
+        let port_i = EXTICR_PORT_I << EXTICR4_LINE_13_SHIFT;
+        ptr::write_volatile(SYSCFG_EXTICR4 as *mut u32, exticr4 & !line_13 | port_i);
+
+        let ftsr = ptr::read_volatile(EXTI_FTSR as *const u32);
+        ptr::write_volatile(EXTI_FTSR as *mut u32, ftsr | EXTI_LINE_TOUCH);
+        ptr::write_volatile(EXTI_PR as *mut u32, EXTI_LINE_TOUCH);
+        let imr = ptr::read_volatile(EXTI_IMR as *const u32);
+        ptr::write_volatile(EXTI_IMR as *mut u32, imr | EXTI_LINE_TOUCH);
+    }
+    TOUCH_INTERRUPT.store(true, Ordering::SeqCst);
+    // there may already be a finger on the screen
+    TOUCH_SIGNALLED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/**
+ * Handler of the EXTI15_10 interrupt.
+ */
+pub fn on_touch_interrupt() {
+    unsafe { ptr::write_volatile(EXTI_PR as *mut u32, EXTI_LINE_TOUCH) };
+    TOUCH_SIGNALLED.store(true, Ordering::SeqCst);
+}
+
+/**
+ * Whether the touch controller signalled since the last call, i.e. whether
+ * it is worth reading. Always true without the touch interrupt.
+ */
+pub fn take_touch_signal() -> bool {
+    TOUCH_SIGNALLED.swap(false, Ordering::SeqCst) || !TOUCH_INTERRUPT.load(Ordering::SeqCst)
+}
+
+/**
+ * Like `take_touch_signal`, but the signal stays.
+ */
+pub fn touch_signalled() -> bool {
+    TOUCH_SIGNALLED.load(Ordering::SeqCst)
+}
+
+/**
+ * Sleeps until the next interrupt, at most until the next ms.
+ */
+pub fn sleep() {
+    unsafe { asm!("wfi" :::: "volatile") };
+}
+
+/**
+ * Sleeps until the tick count reaches `deadline`.
+ */
+pub fn sleep_until(deadline: usize) {
+    while system_clock::ticks() < deadline {
+        sleep();
+    }
+}
@@ -0,0 +1,179 @@
+//! Screenshots as 24 bit BMP files.
+//!
+//! The layers are blended the way the LTDC does it: layer 1 over the
+//! background color, layer 2 over the result, each weighted with its pixel
+//! alpha. The encoder only needs a function returning the pixels, so it runs
+//! on the framebuffers as well as on a `RgbaBuffer` on a PC, and streams the
+//! file row by row instead of keeping a copy of the screen in memory.
+
+use alloc::Vec;
+use canvas::Color;
+
+/// Size of the file header plus the BITMAPINFOHEADER.
+pub const HEADER_SIZE: usize = 54;
+
+/**
+ * Blends `top` over the opaque color `below` using the alpha of `top`.
+ */
+pub fn blend(top: Color, below: Color) -> Color {
+    let alpha = u32::from(top.alpha);
+    let mix = |top: u8, below: u8| {
+        ((u32::from(top) * alpha + u32::from(below) * (255 - alpha) + 127) / 255) as u8
+    };
+    Color::rgb(
+        mix(top.red, below.red),
+        mix(top.green, below.green),
+        mix(top.blue, below.blue),
+    )
+}
+
+/**
+ * The color the display shows for a pixel of both layers.
+ */
+pub fn composite(background: Color, layer_1: Color, layer_2: Color) -> Color {
+    blend(layer_2, blend(layer_1, Color { alpha: 255, ..background }))
+}
+
+/**
+ * Bytes per row in the file, rows are padded to a multiple of 4 bytes.
+ */
+pub fn row_size(width: usize) -> usize {
+    (width * 3 + 3) & !3
+}
+
+/**
+ * Size of the whole file.
+ */
+pub fn file_size(width: usize, height: usize) -> usize {
+    HEADER_SIZE + row_size(width) * height
+}
+
+fn put_u16(out: &mut [u8], at: usize, value: u16) {
+    out[at] = value as u8;
+    out[at + 1] = (value >> 8) as u8;
+}
+
+fn put_u32(out: &mut [u8], at: usize, value: u32) {
+    put_u16(out, at, value as u16);
+    put_u16(out, at + 2, (value >> 16) as u16);
+}
+
+/**
+ * Header of an uncompressed, bottom-up 24 bit bitmap.
+ */
+pub fn header(width: usize, height: usize) -> [u8; HEADER_SIZE] {
+    let mut out = [0; HEADER_SIZE];
+    out[0] = b'B';
+    out[1] = b'M';
+    put_u32(&mut out, 2, file_size(width, height) as u32);
+    put_u32(&mut out, 10, HEADER_SIZE as u32);
+    put_u32(&mut out, 14, 40); // size of the info header
+    put_u32(&mut out, 18, width as u32);
+    put_u32(&mut out, 22, height as u32);
+    put_u16(&mut out, 26, 1); // planes
+    put_u16(&mut out, 28, 24); // bits per pixel
+    put_u32(&mut out, 34, (row_size(width) * height) as u32);
+    // 2835 pixels per meter, about 72 dpi
+    put_u32(&mut out, 38, 2835);
+    put_u32(&mut out, 42, 2835);
+    out
+}
+
+/**
+ * Encodes a `width` x `height` image, `pixel(x, y)` returns the pixels with
+ * the top row at y = 0. The file is passed to `write` in pieces: first the
+ * header, then one row at a time, bottom row first as BMP expects.
+ */
+pub fn encode<P, W>(width: usize, height: usize, mut pixel: P, mut write: W)
+where
+    P: FnMut(usize, usize) -> Color,
+    W: FnMut(&[u8]),
+{
+    write(&header(width, height));
+    let mut row = vec![0; row_size(width)];
+    for y in (0..height).rev() {
+        for x in 0..width {
+            let color = pixel(x, y);
+            row[x * 3] = color.blue;
+            row[x * 3 + 1] = color.green;
+            row[x * 3 + 2] = color.red;
+        }
+        write(&row);
+    }
+}
+
+/**
+ * Encodes the whole image into memory, for images small enough to fit.
+ */
+pub fn encode_to_vec<P: FnMut(usize, usize) -> Color>(width: usize, height: usize, pixel: P) -> Vec<u8> {
+    let mut out = Vec::with_capacity(file_size(width, height));
+    encode(width, height, pixel, |bytes| out.extend_from_slice(bytes));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use canvas::{Canvas, RgbaBuffer};
+
+    fn u16_at(bytes: &[u8], at: usize) -> u16 {
+        u16::from(bytes[at]) | u16::from(bytes[at + 1]) << 8
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from(u16_at(bytes, at)) | u32::from(u16_at(bytes, at + 2)) << 16
+    }
+
+    /// 2 x 2 pixels: red and green on top, blue and white below.
+    fn image() -> RgbaBuffer {
+        let mut buffer = RgbaBuffer::new(2, 2);
+        buffer.set_pixel(0, 0, Color::rgb(255, 0, 0));
+        buffer.set_pixel(1, 0, Color::rgb(0, 255, 0));
+        buffer.set_pixel(0, 1, Color::rgb(0, 0, 255));
+        buffer.set_pixel(1, 1, Color::rgb(255, 255, 255));
+        buffer
+    }
+
+    #[test]
+    fn header_fields() {
+        let buffer = image();
+        let file = encode_to_vec(2, 2, |x, y| buffer.pixel(x, y));
+        assert_eq!(file.len(), file_size(2, 2));
+        assert_eq!(&file[..2], b"BM");
+        assert_eq!(u32_at(&file, 2), file.len() as u32);
+        assert_eq!(u32_at(&file, 10), HEADER_SIZE as u32);
+        assert_eq!(u32_at(&file, 14), 40);
+        assert_eq!(u32_at(&file, 18), 2);
+        // positive, so the rows are stored bottom-up
+        assert_eq!(u32_at(&file, 22), 2);
+        assert_eq!(u16_at(&file, 26), 1);
+        assert_eq!(u16_at(&file, 28), 24);
+        assert_eq!(u32_at(&file, 30), 0);
+        assert_eq!(u32_at(&file, 34), 2 * 8);
+    }
+
+    #[test]
+    fn rows_are_padded_and_bottom_up() {
+        assert_eq!(row_size(1), 4);
+        assert_eq!(row_size(2), 8);
+        assert_eq!(row_size(4), 12);
+        let buffer = image();
+        let file = encode_to_vec(2, 2, |x, y| buffer.pixel(x, y));
+        let pixels = &file[HEADER_SIZE..];
+        // blue, white and padding, then red, green and padding; BGR each
+        assert_eq!(
+            pixels,
+            &[255, 0, 0, 255, 255, 255, 0, 0, 0, 0, 255, 0, 255, 0, 0, 0][..]
+        );
+    }
+
+    #[test]
+    fn streams_the_same_bytes() {
+        let buffer = image();
+        let mut pieces = Vec::new();
+        encode(2, 2, |x, y| buffer.pixel(x, y), |bytes| pieces.push(bytes.to_vec()));
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(pieces[0].len(), HEADER_SIZE);
+        assert_eq!(pieces.concat(), encode_to_vec(2, 2, |x, y| buffer.pixel(x, y)));
+    }
+}
@@ -0,0 +1,69 @@
+//! Files on the host of the debugger, written over ARM semihosting.
+//!
+//! Like `hprintln!`, this only works while a debugger with semihosting
+//! enabled is attached, e.g. `monitor arm semihosting enable` in OpenOCD.
+//! Relative paths are relative to the working directory of OpenOCD.
+
+use alloc::Vec;
+
+const SYS_OPEN: usize = 0x01;
+const SYS_CLOSE: usize = 0x02;
+const SYS_WRITE: usize = 0x05;
+/// `fopen` mode "wb"
+const MODE_WRITE_BINARY: usize = 5;
+
+unsafe fn call(operation: usize, args: &[usize]) -> isize {
+    let result: isize;
+    asm!("bkpt 0xAB"
+         : "={r0}"(result)
+         : "{r0}"(operation), "{r1}"(args.as_ptr())
+         : "memory"
+         : "volatile");
+    result
+}
+
+/**
+ * A file opened for writing on the host.
+ */
+pub struct File {
+    handle: usize,
+}
+
+impl File {
+    /**
+     * Creates or truncates the file at `path`.
+     */
+    pub fn create(path: &str) -> Result<File, ()> {
+        // the host expects a null terminated name
+        let mut name: Vec<u8> = path.bytes().collect();
+        name.push(0);
+        let args = [name.as_ptr() as usize, MODE_WRITE_BINARY, path.len()];
+        let handle = unsafe { call(SYS_OPEN, &args) };
+        if handle < 0 {
+            Err(())
+        } else {
+            Ok(File {
+                handle: handle as usize,
+            })
+        }
+    }
+
+    /**
+     * Writes all of `data`.
+     */
+    pub fn write_all(&mut self, data: &[u8]) -> Result<(), ()> {
+        let args = [self.handle, data.as_ptr() as usize, data.len()];
+        // returns the number of bytes that were not written
+        match unsafe { call(SYS_WRITE, &args) } {
+            0 => Ok(()),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let args = [self.handle];
+        unsafe { call(SYS_CLOSE, &args) };
+    }
+}
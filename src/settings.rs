@@ -0,0 +1,94 @@
+//! Settings that are kept when the board restarts or is reset.
+//!
+//! They live in the backup SRAM, which keeps its content as long as the board
+//! has power (or the backup battery, if one is fitted).
+//! A magic word tells whether the settings were ever written, the backup SRAM
+//! holds garbage after power up, and a version word whether they were written
+//! by a build with the same layout. Settings that fail either check are not
+//! used.
+
+use core::ptr;
+use touch_filter::Calibration;
+
+/// Marks settings that were written.
+const MAGIC: u32 = 0x5e77_1265;
+/// Layout of `Settings`, has to change with every change of it.
+const VERSION: u32 = 1;
+
+/// RCC AHB1 peripheral clock enable register.
+const RCC_AHB1ENR: usize = 0x4002_3830;
+/// Clock of the backup SRAM.
+const AHB1ENR_BKPSRAMEN: u32 = 1 << 18;
+/// RCC APB1 peripheral clock enable register.
+const RCC_APB1ENR: usize = 0x4002_3840;
+/// Clock of the power controller.
+const APB1ENR_PWREN: u32 = 1 << 28;
+/// Power control register 1.
+const PWR_CR1: usize = 0x4000_7000;
+/// Disables the write protection of the backup domain.
+const CR1_DBP: u32 = 1 << 8;
+/// Start of the 4 KB backup SRAM, the settings are at its beginning.
+const BKPSRAM_START: usize = 0x4002_4000;
+
+/**
+ * The settings as they are laid out in the backup SRAM.
+ */
+#[repr(C)]
+struct Settings {
+    magic: u32,
+    version: u32,
+    calibration: Calibration,
+}
+
+/**
+ * Gives access to the backup SRAM. Has to be called before the other
+ * functions in here.
+ */
+pub fn init() {
+    unsafe {
+        let apb1enr = ptr::read_volatile(RCC_APB1ENR as *const u32);
+        ptr::write_volatile(RCC_APB1ENR as *mut u32, apb1enr | APB1ENR_PWREN);
+        let cr1 = ptr::read_volatile(PWR_CR1 as *const u32);
+        ptr::write_volatile(PWR_CR1 as *mut u32, cr1 | CR1_DBP);
+        let ahb1enr = ptr::read_volatile(RCC_AHB1ENR as *const u32);
+        ptr::write_volatile(RCC_AHB1ENR as *mut u32, ahb1enr | AHB1ENR_BKPSRAMEN);
+    }
+}
+
+unsafe fn settings() -> &'static mut Settings {
+    &mut *(BKPSRAM_START as *mut Settings)
+}
+
+/**
+ * The calibration saved by `save_calibration`, `None` if there is none or it
+ * makes no sense.
+ */
+pub fn load_calibration() -> Option<Calibration> {
+    let settings = unsafe { settings() };
+    let (magic, version, calibration) = unsafe {
+        (
+            ptr::read_volatile(&settings.magic),
+            ptr::read_volatile(&settings.version),
+            ptr::read_volatile(&settings.calibration),
+        )
+    };
+    if magic == MAGIC && version == VERSION && calibration.is_plausible() {
+        Some(calibration)
+    } else {
+        None
+    }
+}
+
+/**
+ * Keeps the calibration for the next start.
+ */
+pub fn save_calibration(calibration: Calibration) {
+    let settings = unsafe { settings() };
+    unsafe {
+        // a reset in between leaves no half written settings behind
+        ptr::write_volatile(&mut settings.magic, 0);
+        ptr::write_volatile(&mut settings.calibration, calibration);
+        ptr::write_volatile(&mut settings.version, VERSION);
+        ptr::write_volatile(&mut settings.magic, MAGIC);
+    }
+}
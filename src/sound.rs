@@ -0,0 +1,191 @@
+//! Plays the sound effects and music of `mixer` on the headphone jack.
+//!
+//! The WM8994 codec gets 16 bit stereo samples from block A of SAI2, fed by
+//! stream 4 of DMA2 from a buffer in two halves: while the DMA plays one half,
+//! `Sound::update` mixes the next samples into the other one.
+
+use alloc::Vec;
+use core::ptr;
+use mixer::{Effect, Mixer};
+use music::SongError;
+use scheduler;
+use stm32f7::{i2c, system_clock};
+
+/// Frames per half of the buffer, 1024 frames last 64 ms at 16 kHz.
+const HALF_FRAMES: usize = 1024;
+
+/// 7 bit I2C address of the WM8994 codec.
+const WM8994_ADDRESS: u8 = 0b001_1010;
+
+/// Register writes that turn on the headphone output after
+/// `audio::init_wm8994` set up the interface, with the time in ms to wait
+/// after each.
+static HEADPHONE_SETUP: [(u16, u16, usize); 11] = [
+    // AIF1 DAC1 left and right, DAC1 left and right on
+    (0x0005, 0x0303, 0),
+    // AIF1 DAC1 to the DAC1 mixers
+    (0x0601, 0x0001, 0),
+    (0x0602, 0x0001, 0),
+    // DAC1 to the headphone output mixers
+    (0x002d, 0x0100, 0),
+    (0x002e, 0x0100, 0),
+    // headphone cold start sequence: charge pump, DC servo, output stage
+    (0x0110, 0x8100, 300),
+    // unmute AIF1 DAC1
+    (0x0420, 0x0000, 0),
+    // DAC1 at 0 dB, update both channels
+    (0x0610, 0x00c0, 0),
+    (0x0611, 0x01c0, 0),
+    // headphone volume -9 dB and unmuted, update both channels
+    (0x001c, 0x0070, 0),
+    (0x001d, 0x0170, 0),
+];
+
+/// RCC AHB1 peripheral clock enable register.
+const RCC_AHB1ENR: usize = 0x4002_3830;
+/// Clock of DMA2.
+const AHB1ENR_DMA2EN: u32 = 1 << 22;
+
+/// Base address of the DMA2 registers.
+const DMA2_START: usize = 0x4002_6400;
+/// High interrupt status register, streams 4 to 7.
+const DMA2_HISR: usize = DMA2_START + 0x04;
+/// High interrupt flag clear register.
+const DMA2_HIFCR: usize = DMA2_START + 0x0c;
+/// Stream 4 configuration register, the other stream registers follow it.
+const DMA2_S4CR: usize = DMA2_START + 0x10 + 0x18 * 4;
+/// Stream 4 number of data items register.
+const DMA2_S4NDTR: usize = DMA2_S4CR + 0x04;
+/// Stream 4 peripheral address register.
+const DMA2_S4PAR: usize = DMA2_S4CR + 0x08;
+/// Stream 4 memory 0 address register.
+const DMA2_S4M0AR: usize = DMA2_S4CR + 0x0c;
+/// Stream 4 half transfer flag in HISR and HIFCR.
+const HTIF4: u32 = 1 << 4;
+/// Stream 4 transfer complete flag in HISR and HIFCR.
+const TCIF4: u32 = 1 << 5;
+/// Channel 3 (SAI2 A), high priority, 16 bit memory and peripheral size,
+/// memory increment, circular, memory to peripheral.
+const S4CR_CONFIG: u32 = 3 << 25 | 0b10 << 16 | 0b01 << 13 | 0b01 << 11 | 1 << 10 | 1 << 8
+    | 0b01 << 6;
+/// Stream enable bit of the configuration register.
+const SXCR_EN: u32 = 1;
+
+/// Base address of the SAI2 registers.
+const SAI2_START: usize = 0x4001_5c00;
+/// Block A configuration register 1.
+const SAI2_ACR1: usize = SAI2_START + 0x04;
+/// Block A data register.
+const SAI2_ADR: usize = SAI2_START + 0x20;
+/// Audio block enable.
+const ACR1_SAIEN: u32 = 1 << 16;
+/// DMA requests of the block.
+const ACR1_DMAEN: u32 = 1 << 17;
+
+pub struct Sound {
+    mixer: Mixer,
+    /// both halves, interleaved stereo samples
+    buffer: Vec<i16>,
+}
+
+impl Sound {
+    /**
+     * Turns on the headphone output of the codec and starts streaming
+     * silence. SAI2 and the codec have to be set up with `audio::init_sai_2`
+     * and `audio::init_wm8994` first.
+     */
+    pub fn new(i2c_3: &mut i2c::I2C) -> Result<Sound, i2c::Error> {
+        enable_headphones(i2c_3)?;
+        let sound = Sound {
+            mixer: Mixer::new(),
+            buffer: vec![0; 2 * HALF_FRAMES * 2],
+        };
+        unsafe {
+            sound.start_dma();
+        }
+        Ok(sound)
+    }
+
+    /**
+     * Starts an effect, it is heard with the next half of the buffer.
+     */
+    pub fn play(&mut self, effect: Effect) {
+        self.mixer.play(effect);
+    }
+
+    /**
+     * Plays a song in a loop, see `music`.
+     */
+    pub fn play_music(&mut self, song: &'static str) -> Result<(), SongError> {
+        self.mixer.play_music(song)
+    }
+
+    /**
+     * Speed of the music in percent of the tempo of the song.
+     */
+    pub fn set_tempo(&mut self, percent: u32) {
+        self.mixer.set_tempo(percent);
+    }
+
+    /**
+     * Mixes new samples into the halves the DMA has played since the last
+     * call. Has to be called at least once per half, i.e. every 64 ms.
+     */
+    pub fn update(&mut self) {
+        let half = HALF_FRAMES * 2;
+        let flags = unsafe { ptr::read_volatile(DMA2_HISR as *const u32) };
+        if flags & HTIF4 != 0 {
+            unsafe { ptr::write_volatile(DMA2_HIFCR as *mut u32, HTIF4) };
+            self.mixer.fill(&mut self.buffer[..half]);
+        }
+        if flags & TCIF4 != 0 {
+            unsafe { ptr::write_volatile(DMA2_HIFCR as *mut u32, TCIF4) };
+            self.mixer.fill(&mut self.buffer[half..]);
+        }
+    }
+
+    /**
+     * Lets DMA2 stream 4 copy the buffer to SAI2 block A over and over.
+     */
+    unsafe fn start_dma(&self) {
+        let ahb1enr = ptr::read_volatile(RCC_AHB1ENR as *const u32);
+        ptr::write_volatile(RCC_AHB1ENR as *mut u32, ahb1enr | AHB1ENR_DMA2EN);
+
+        ptr::write_volatile(DMA2_S4CR as *mut u32, 0);
+        while ptr::read_volatile(DMA2_S4CR as *const u32) & SXCR_EN != 0 {}
+        ptr::write_volatile(DMA2_HIFCR as *mut u32, 0b11_1101);
+        ptr::write_volatile(DMA2_S4PAR as *mut u32, SAI2_ADR as u32);
+        ptr::write_volatile(DMA2_S4M0AR as *mut u32, self.buffer.as_ptr() as u32);
+        ptr::write_volatile(DMA2_S4NDTR as *mut u32, self.buffer.len() as u32);
+        ptr::write_volatile(DMA2_S4CR as *mut u32, S4CR_CONFIG);
+        ptr::write_volatile(DMA2_S4CR as *mut u32, S4CR_CONFIG | SXCR_EN);
+
+        let acr1 = ptr::read_volatile(SAI2_ACR1 as *const u32);
+        ptr::write_volatile(SAI2_ACR1 as *mut u32, acr1 | ACR1_DMAEN | ACR1_SAIEN);
+    }
+}
+
+fn enable_headphones(i2c_3: &mut i2c::I2C) -> Result<(), i2c::Error> {
+    i2c_3.connect::<u16, _>(i2c::Address::bits_7(WM8994_ADDRESS), |mut conn| {
+        for &(register, value, wait) in HEADPHONE_SETUP.iter() {
+            conn.write(register, value)?;
+            if wait > 0 {
+                system_clock::wait(wait);
+            }
+        }
+        Ok(())
+    })
+}
+
+/**
+ * Waits like `system_clock::wait`, but keeps mixing while it does.
+ */
+pub fn wait(sound: &mut Option<Sound>, ms: usize) {
+    let end = system_clock::ticks() + ms;
+    while system_clock::ticks() < end {
+        if let Some(ref mut sound) = *sound {
+            sound.update();
+        }
+        scheduler::sleep();
+    }
+}
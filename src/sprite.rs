@@ -0,0 +1,118 @@
+use core::marker::PhantomData;
+use rle;
+
+/**
+ * Clockwise rotation of a sprite.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RotDirection {
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+impl RotDirection {
+    /**
+     * Where the point x,y of an area of the given size ends up when the area
+     * is rotated.
+     */
+    pub fn rotate_point(
+        self,
+        (x, y): (usize, usize),
+        (width, height): (usize, usize),
+    ) -> (usize, usize) {
+        match self {
+            RotDirection::R0 => (x, y),
+            RotDirection::R90 => (height - 1 - y, x),
+            RotDirection::R180 => (width - 1 - x, height - 1 - y),
+            RotDirection::R270 => (y, width - 1 - x),
+        }
+    }
+
+    /**
+     * The rotation that turns back by the same angle.
+     */
+    pub fn inverse(self) -> RotDirection {
+        match self {
+            RotDirection::R90 => RotDirection::R270,
+            RotDirection::R270 => RotDirection::R90,
+            rot => rot,
+        }
+    }
+}
+
+/**
+ * A picture that was decoded from a bitmap by `build.rs`.\n
+ * Pixels are stored run-length encoded (see `rle`), row by row, top row first,
+ * already in the format of the layer the sprite is drawn to (`u32` for
+ * ARGB8888, `u16` for AL88). A pixel with an alpha of zero is transparent.
+ */
+pub struct Sprite<P: 'static> {
+    pub width: usize,
+    pub height: usize,
+    pub data: &'static [u8],
+    pub pixel: PhantomData<P>,
+}
+
+impl<P: rle::Pixel> Sprite<P> {
+    /**
+     * Decodes the pixels one by one, row by row.
+     */
+    pub fn pixels(&self) -> rle::Decoder<'static, P> {
+        rle::Decoder::new(self.data)
+    }
+
+    /**
+     * Decodes the pixels together with their position after rotating the
+     * sprite clockwise.
+     */
+    pub fn rotated_pixels(&self, rot: RotDirection) -> RotatedPixels<P> {
+        RotatedPixels {
+            decoder: self.pixels(),
+            width: self.width,
+            height: self.height,
+            x: 0,
+            y: 0,
+            rot,
+        }
+    }
+
+    /**
+     * Width and height of the sprite after rotating it.
+     */
+    pub fn rotated_size(&self, rot: &RotDirection) -> (usize, usize) {
+        match *rot {
+            RotDirection::R0 | RotDirection::R180 => (self.width, self.height),
+            RotDirection::R90 | RotDirection::R270 => (self.height, self.width),
+        }
+    }
+}
+
+/**
+ * Iterator over `(x, y, pixel)` of a rotated sprite, see `Sprite::rotated_pixels`.
+ */
+pub struct RotatedPixels<P: rle::Pixel> {
+    decoder: rle::Decoder<'static, P>,
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+    rot: RotDirection,
+}
+
+impl<P: rle::Pixel> Iterator for RotatedPixels<P> {
+    type Item = (usize, usize, P);
+
+    fn next(&mut self) -> Option<(usize, usize, P)> {
+        let pixel = self.decoder.next()?;
+        let (x, y) = (self.x, self.y);
+        self.x += 1;
+        if self.x == self.width {
+            self.x = 0;
+            self.y += 1;
+        }
+        let (to_x, to_y) = self.rot.rotate_point((x, y), (self.width, self.height));
+        Some((to_x, to_y, pixel))
+    }
+}
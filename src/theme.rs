@@ -0,0 +1,137 @@
+//! Color themes.
+//!
+//! All colors the game draws with come from a `Theme`, the sprites only
+//! bring their shapes: the snake sprites are grey and tinted with the color of
+//! their player. Besides the classic look there are palettes that stay
+//! distinguishable with red-green color blindness (based on the Okabe-Ito
+//! palette) and a high contrast one.
+
+use canvas::Color;
+use core::ptr;
+use ui::Style;
+
+pub struct Theme {
+    pub name: &'static str,
+    /// LTDC background color, shows wherever layer 1 is transparent
+    pub background: Color,
+    /// the line around the field
+    pub frame: Color,
+    /// the background flashes in this color when a snake bites itself
+    pub alarm: Color,
+    /// snake color of each player, see `player_color`
+    pub players: [Color; 4],
+    /// menu widgets, layer 2 only shows their brightness
+    pub ui: Style,
+}
+
+impl Theme {
+    /**
+     * Color of player `player`, the colors repeat if there are more players
+     * than colors.
+     */
+    pub fn player_color(&self, player: usize) -> Color {
+        self.players[player % self.players.len()]
+    }
+}
+
+/// An opaque color that can be used in statics.
+macro_rules! rgb {
+    ($red:expr, $green:expr, $blue:expr) => {
+        Color {
+            red: $red,
+            green: $green,
+            blue: $blue,
+            alpha: 255,
+        }
+    };
+}
+
+const GREY_UI: Style = Style {
+    foreground: rgb!(255, 255, 255),
+    background: rgb!(60, 60, 60),
+    pressed: rgb!(150, 150, 150),
+};
+
+pub static CLASSIC: Theme = Theme {
+    name: "classic",
+    background: rgb!(0xb0, 0x77, 0x08),
+    frame: rgb!(0, 0, 0),
+    alarm: rgb!(255, 0, 0),
+    players: [
+        rgb!(121, 121, 121),
+        rgb!(40, 90, 200),
+        rgb!(30, 140, 60),
+        rgb!(150, 40, 150),
+    ],
+    ui: GREY_UI,
+};
+
+/// For deuteranopia: the red apple turns brownish yellow, so the field is a
+/// dark blue it stands out from.
+pub static DEUTERANOPIA: Theme = Theme {
+    name: "deuteranopia",
+    background: rgb!(0x1f, 0x2f, 0x5a),
+    frame: rgb!(255, 255, 255),
+    alarm: rgb!(0xd5, 0x5e, 0x00),
+    players: [
+        rgb!(0xe6, 0x9f, 0x00),
+        rgb!(0x56, 0xb4, 0xe9),
+        rgb!(0xf0, 0xe4, 0x42),
+        rgb!(0xcc, 0x79, 0xa7),
+    ],
+    ui: GREY_UI,
+};
+
+/// For protanopia: red looks dark, so the field is light and the apple keeps
+/// its contrast.
+pub static PROTANOPIA: Theme = Theme {
+    name: "protanopia",
+    background: rgb!(0xe8, 0xe8, 0xe0),
+    frame: rgb!(0, 0, 0),
+    alarm: rgb!(0x00, 0x72, 0xb2),
+    players: [
+        rgb!(0x00, 0x72, 0xb2),
+        rgb!(0xe6, 0x9f, 0x00),
+        rgb!(0x00, 0x9e, 0x73),
+        rgb!(0xcc, 0x79, 0xa7),
+    ],
+    ui: GREY_UI,
+};
+
+pub static HIGH_CONTRAST: Theme = Theme {
+    name: "high contrast",
+    background: rgb!(0, 0, 0),
+    frame: rgb!(255, 255, 255),
+    alarm: rgb!(255, 255, 255),
+    players: [
+        rgb!(255, 255, 255),
+        rgb!(255, 255, 0),
+        rgb!(0, 255, 255),
+        rgb!(255, 0, 255),
+    ],
+    ui: Style {
+        foreground: rgb!(255, 255, 255),
+        background: rgb!(0, 0, 0),
+        pressed: rgb!(160, 160, 160),
+    },
+};
+
+/// All themes in the order the menu cycles through them.
+pub static THEMES: [&Theme; 4] = [&CLASSIC, &DEUTERANOPIA, &PROTANOPIA, &HIGH_CONTRAST];
+
+/**
+ * The position of `theme` in `THEMES`, 0 if it is not in there.
+ */
+pub fn index(theme: &'static Theme) -> usize {
+    THEMES
+        .iter()
+        .position(|&t| ptr::eq(t, theme))
+        .unwrap_or(0)
+}
+
+/**
+ * The theme after `theme` in `THEMES`.
+ */
+pub fn next(theme: &'static Theme) -> &'static Theme {
+    THEMES[(index(theme) + 1) % THEMES.len()]
+}
@@ -0,0 +1,403 @@
+//! Cleaning up the points the touch controller reports.
+//!
+//! A finger only counts once it was seen for `MIN_HOLD` ms, so a single noisy
+//! sample neither turns a snake nor opens the pause menu, and it only counts
+//! as lifted once it was gone for `RELEASE_DELAY` ms, so a sample that misses
+//! it does not release a button. The controller driver reports no pressure,
+//! the filter goes by time and distance only. Nothing in here touches the
+//! hardware, `Game` passes in the points it reads.
+
+use alloc::Vec;
+
+/// A finger has to stay this many ms to count as pressed.
+pub const MIN_HOLD: usize = 30;
+/// A pressed finger has to be gone for this many ms to count as lifted.
+pub const RELEASE_DELAY: usize = 40;
+/// Points in consecutive samples closer than this many pixels (on both axes)
+/// are the same finger.
+const SAME_FINGER_DISTANCE: i32 = 40;
+/// Fingers the controller can report at the same time.
+const MAX_FINGERS: usize = 5;
+
+/**
+ * Corrects points of a touch screen that is off: `point * scale / 1024 +
+ * offset` on each axis.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+pub struct Calibration {
+    pub offset: (i32, i32),
+    pub scale: (i32, i32),
+}
+
+/// Scale factor 1 of `Calibration`.
+const SCALE_ONE: i32 = 1024;
+/// Scales further from 1 than this factor mean that a target was missed.
+const MAX_SCALE_FACTOR: i32 = 2;
+
+impl Calibration {
+    /**
+     * Leaves all points as they are.
+     */
+    pub fn identity() -> Calibration {
+        Calibration {
+            offset: (0, 0),
+            scale: (SCALE_ONE, SCALE_ONE),
+        }
+    }
+
+    /**
+     * The corrected point, inside of a screen of the given size.
+     */
+    pub fn apply(&self, (x, y): (u16, u16), (width, height): (usize, usize)) -> (u16, u16) {
+        let axis = |value: u16, scale: i32, offset: i32, size: usize| {
+            let value = i32::from(value) * scale / SCALE_ONE + offset;
+            value.max(0).min(size as i32 - 1) as u16
+        };
+        (
+            axis(x, self.scale.0, self.offset.0, width),
+            axis(y, self.scale.1, self.offset.1, height),
+        )
+    }
+
+    /**
+     * Whether the scales are in the range `from_taps` can return, a saved
+     * calibration that is not was damaged.
+     */
+    pub fn is_plausible(&self) -> bool {
+        let plausible = |scale: i32| {
+            scale >= SCALE_ONE / MAX_SCALE_FACTOR && scale <= SCALE_ONE * MAX_SCALE_FACTOR
+        };
+        plausible(self.scale.0) && plausible(self.scale.1)
+    }
+
+    /**
+     * The calibration that moves the points tapped as close as possible to
+     * the targets shown, fitted on each axis on its own. `None` if the taps
+     * are too close together or would need a scale far from 1, which means
+     * that a target was missed.
+     */
+    pub fn from_taps(targets: &[(u16, u16)], taps: &[(u16, u16)]) -> Option<Calibration> {
+        if targets.len() != taps.len() || targets.len() < 2 {
+            return None;
+        }
+        let axis = |points: &[(u16, u16)], y: bool| -> Vec<u16> {
+            points.iter().map(|&(px, py)| if y { py } else { px }).collect()
+        };
+        let (scale_x, offset_x) = fit_axis(&axis(targets, false), &axis(taps, false))?;
+        let (scale_y, offset_y) = fit_axis(&axis(targets, true), &axis(taps, true))?;
+        Some(Calibration {
+            offset: (offset_x, offset_y),
+            scale: (scale_x, scale_y),
+        })
+    }
+}
+
+/**
+ * Least squares fit of `target = tap * scale / SCALE_ONE + offset`, returns
+ * the scale and the offset.
+ */
+fn fit_axis(targets: &[u16], taps: &[u16]) -> Option<(i32, i32)> {
+    let count = targets.len() as i64;
+    let target_mean = targets.iter().map(|&t| i64::from(t)).sum::<i64>() / count;
+    let tap_mean = taps.iter().map(|&t| i64::from(t)).sum::<i64>() / count;
+    let (mut covariance, mut variance) = (0, 0);
+    for (&target, &tap) in targets.iter().zip(taps.iter()) {
+        let tap_delta = i64::from(tap) - tap_mean;
+        covariance += tap_delta * (i64::from(target) - target_mean);
+        variance += tap_delta * tap_delta;
+    }
+    if variance == 0 {
+        return None;
+    }
+    let one = i64::from(SCALE_ONE);
+    let factor = i64::from(MAX_SCALE_FACTOR);
+    let scale = covariance * one / variance;
+    if scale < one / factor || scale > one * factor {
+        return None;
+    }
+    let offset = target_mean - tap_mean * scale / one;
+    Some((scale as i32, offset as i32))
+}
+
+/**
+ * A finger the filter follows.
+ */
+#[derive(Clone, Copy, Debug)]
+struct Finger {
+    point: (u16, u16),
+    first_seen: usize,
+    last_seen: usize,
+    /// it stayed for `MIN_HOLD` ms
+    pressed: bool,
+    /// `touches` returned it
+    reported: bool,
+}
+
+/**
+ * Follows the fingers on the screen from sample to sample.
+ */
+pub struct TouchFilter {
+    fingers: Vec<Finger>,
+    /// fingers that were pressed and lifted without `touches` returning
+    /// them, since the last `take_taps`
+    taps: Vec<(u16, u16)>,
+}
+
+impl TouchFilter {
+    pub fn new() -> TouchFilter {
+        TouchFilter {
+            fingers: Vec::new(),
+            taps: Vec::new(),
+        }
+    }
+
+    /**
+     * Takes the points of a new sample, taken at `now` ms.
+     */
+    pub fn update(&mut self, points: &[(u16, u16)], now: usize) {
+        // fingers of the last samples that got a point of this one
+        let mut seen = vec![false; self.fingers.len()];
+        for &point in points.iter().take(MAX_FINGERS) {
+            let nearest = self.fingers
+                .iter()
+                .enumerate()
+                .filter(|&(index, finger)| {
+                    index < seen.len() && !seen[index] && is_near(finger.point, point)
+                })
+                .map(|(index, _)| index)
+                .next();
+            match nearest {
+                Some(index) => {
+                    let finger = &mut self.fingers[index];
+                    finger.point = point;
+                    finger.last_seen = now;
+                    finger.pressed = finger.pressed || now - finger.first_seen >= MIN_HOLD;
+                    seen[index] = true;
+                }
+                None => self.fingers.push(Finger {
+                    point,
+                    first_seen: now,
+                    last_seen: now,
+                    pressed: false,
+                    reported: false,
+                }),
+            }
+        }
+        let taps = &mut self.taps;
+        self.fingers.retain(|finger| {
+            let gone = now - finger.last_seen;
+            if finger.pressed && !finger.reported && gone >= RELEASE_DELAY {
+                taps.push(finger.point);
+            }
+            // a finger that was never pressed was noise
+            gone < RELEASE_DELAY && (finger.pressed || gone == 0)
+        });
+    }
+
+    /**
+     * The points of the fingers that are pressed.
+     */
+    pub fn touches(&mut self) -> Vec<(u16, u16)> {
+        let mut touches = Vec::new();
+        for finger in self.fingers.iter_mut().filter(|finger| finger.pressed) {
+            finger.reported = true;
+            touches.push(finger.point);
+        }
+        touches
+    }
+
+    /**
+     * Where fingers were lifted that `touches` never returned since the last
+     * call, so touches that were shorter than the time between two calls of
+     * `touches` count as well.
+     */
+    pub fn take_taps(&mut self) -> Vec<(u16, u16)> {
+        let mut taps = Vec::new();
+        taps.append(&mut self.taps);
+        taps
+    }
+
+    /**
+     * Forgets all fingers, a finger still on the screen has to be held for
+     * `MIN_HOLD` ms again.
+     */
+    pub fn reset(&mut self) {
+        self.fingers.clear();
+        self.taps.clear();
+    }
+}
+
+fn is_near((x, y): (u16, u16), (other_x, other_y): (u16, u16)) -> bool {
+    (i32::from(x) - i32::from(other_x)).abs() < SAME_FINGER_DISTANCE
+        && (i32::from(y) - i32::from(other_y)).abs() < SAME_FINGER_DISTANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGETS: [(u16, u16); 5] = [(40, 40), (440, 40), (240, 136), (40, 232), (440, 232)];
+
+    fn taps(scale: (i32, i32), offset: (i32, i32)) -> Vec<(u16, u16)> {
+        // the inverse of `Calibration::apply`
+        TARGETS
+            .iter()
+            .map(|&(x, y)| {
+                (
+                    ((i32::from(x) - offset.0) * SCALE_ONE / scale.0) as u16,
+                    ((i32::from(y) - offset.1) * SCALE_ONE / scale.1) as u16,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fit_of_exact_taps() {
+        assert_eq!(fit_axis(&[10, 20, 30], &[10, 20, 30]), Some((SCALE_ONE, 0)));
+        assert_eq!(fit_axis(&[10, 20, 30], &[0, 10, 20]), Some((SCALE_ONE, 10)));
+        assert_eq!(fit_axis(&[20, 40, 60], &[10, 20, 30]), Some((2 * SCALE_ONE, 0)));
+    }
+
+    #[test]
+    fn fit_of_noisy_taps() {
+        let (scale, offset) = fit_axis(&[40, 240, 440], &[42, 238, 441]).unwrap();
+        assert!((scale - SCALE_ONE).abs() < 20, "{}", scale);
+        assert!(offset.abs() < 5, "{}", offset);
+    }
+
+    #[test]
+    fn fit_needs_spread_taps() {
+        assert_eq!(fit_axis(&[10, 20, 30], &[15, 15, 15]), None);
+    }
+
+    #[test]
+    fn fit_rejects_far_scales() {
+        assert_eq!(fit_axis(&[10, 20, 30], &[10, 40, 70]), None);
+        assert_eq!(fit_axis(&[10, 20, 30], &[10, 12, 14]), None);
+        // a tap on the wrong cross
+        assert_eq!(fit_axis(&[40, 240, 440], &[440, 240, 40]), None);
+    }
+
+    #[test]
+    fn calibration_from_taps() {
+        let calibration = Calibration::from_taps(&TARGETS, &taps((900, 1100), (20, -10))).unwrap();
+        assert!(calibration.is_plausible());
+        for (&target, tap) in TARGETS.iter().zip(taps((900, 1100), (20, -10))) {
+            let (x, y) = calibration.apply(tap, (480, 272));
+            assert!((i32::from(x) - i32::from(target.0)).abs() <= 1, "{:?}", (x, target));
+            assert!((i32::from(y) - i32::from(target.1)).abs() <= 1, "{:?}", (y, target));
+        }
+        assert_eq!(
+            Calibration::from_taps(&TARGETS, &taps((SCALE_ONE, SCALE_ONE), (0, 0))),
+            Some(Calibration::identity())
+        );
+    }
+
+    #[test]
+    fn calibration_needs_a_tap_per_target() {
+        assert_eq!(Calibration::from_taps(&TARGETS, &TARGETS[..4]), None);
+        assert_eq!(Calibration::from_taps(&TARGETS[..1], &TARGETS[..1]), None);
+    }
+
+    #[test]
+    fn apply_stays_on_the_screen() {
+        let calibration = Calibration {
+            offset: (-50, 50),
+            scale: (SCALE_ONE, SCALE_ONE),
+        };
+        assert_eq!(calibration.apply((10, 250), (480, 272)), (0, 271));
+        assert_eq!(Calibration::identity().apply((479, 271), (480, 272)), (479, 271));
+    }
+
+    #[test]
+    fn plausible_scales() {
+        assert!(Calibration::identity().is_plausible());
+        let scaled = |scale| Calibration {
+            offset: (0, 0),
+            scale: (SCALE_ONE, scale),
+        };
+        assert!(scaled(SCALE_ONE / 2).is_plausible());
+        assert!(scaled(SCALE_ONE * 2).is_plausible());
+        assert!(!scaled(SCALE_ONE / 2 - 1).is_plausible());
+        assert!(!scaled(SCALE_ONE * 2 + 1).is_plausible());
+        assert!(!scaled(0).is_plausible());
+    }
+
+    /// Feeds `point` from `from` to `to` ms, a sample every ms.
+    fn hold(filter: &mut TouchFilter, point: (u16, u16), from: usize, to: usize) {
+        for now in from..to {
+            filter.update(&[point], now);
+        }
+    }
+
+    /// Feeds samples without fingers from `from` to `to` ms.
+    fn lift(filter: &mut TouchFilter, from: usize, to: usize) {
+        for now in from..to {
+            filter.update(&[], now);
+        }
+    }
+
+    #[test]
+    fn pressed_after_min_hold() {
+        let mut filter = TouchFilter::new();
+        hold(&mut filter, (100, 100), 0, MIN_HOLD);
+        assert!(filter.touches().is_empty());
+        filter.update(&[(100, 100)], MIN_HOLD);
+        assert_eq!(filter.touches(), vec![(100, 100)]);
+    }
+
+    #[test]
+    fn short_touches_are_noise() {
+        let mut filter = TouchFilter::new();
+        hold(&mut filter, (100, 100), 0, MIN_HOLD);
+        lift(&mut filter, MIN_HOLD, MIN_HOLD + RELEASE_DELAY + 10);
+        assert!(filter.touches().is_empty());
+        assert!(filter.take_taps().is_empty());
+    }
+
+    #[test]
+    fn released_after_release_delay() {
+        let mut filter = TouchFilter::new();
+        // last seen at 49 ms
+        hold(&mut filter, (100, 100), 0, 50);
+        lift(&mut filter, 50, 49 + RELEASE_DELAY);
+        assert_eq!(filter.touches(), vec![(100, 100)]);
+        filter.update(&[], 49 + RELEASE_DELAY);
+        assert!(filter.touches().is_empty());
+    }
+
+    #[test]
+    fn a_missed_sample_keeps_the_finger() {
+        let mut filter = TouchFilter::new();
+        hold(&mut filter, (100, 100), 0, 50);
+        lift(&mut filter, 50, 60);
+        hold(&mut filter, (105, 98), 60, 200);
+        assert_eq!(filter.touches(), vec![(105, 98)]);
+        lift(&mut filter, 200, 200 + RELEASE_DELAY + 1);
+        assert!(filter.touches().is_empty());
+        // `touches` returned it, it is no tap
+        assert!(filter.take_taps().is_empty());
+    }
+
+    #[test]
+    fn unreported_touches_are_taps() {
+        let mut filter = TouchFilter::new();
+        hold(&mut filter, (100, 100), 0, 50);
+        lift(&mut filter, 50, 50 + RELEASE_DELAY + 1);
+        assert_eq!(filter.take_taps(), vec![(100, 100)]);
+        assert!(filter.take_taps().is_empty());
+    }
+
+    #[test]
+    fn fingers_apart_are_followed_separately() {
+        let mut filter = TouchFilter::new();
+        hold(&mut filter, (100, 100), 0, 20);
+        for now in 20..60 {
+            filter.update(&[(100, 100), (300, 200)], now);
+        }
+        assert_eq!(filter.touches(), vec![(100, 100), (300, 200)]);
+        filter.reset();
+        assert!(filter.touches().is_empty());
+    }
+}
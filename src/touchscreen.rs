@@ -0,0 +1,171 @@
+//! Reading the touch screen without panicking on I2C errors.
+//!
+//! A failed read is retried right away a few times. When several reads in a
+//! row fail, the bus is recovered: a device that got stuck in the middle of a
+//! transfer may hold SDA low forever, so SCL and SDA are switched to plain
+//! GPIO, clocked nine times to let it finish the byte it is sending, and a
+//! STOP ends the transfer. Then I2C3 is set up again from scratch. The game
+//! shows a warning until the touch controller answers again.
+
+use alloc::Vec;
+use core::ptr;
+use profile;
+use stm32f7::{board, i2c, touch};
+
+/// Attempts of a read before it counts as failed.
+const ATTEMPTS: usize = 3;
+/// The bus is reset after this many failed reads in a row, and again after
+/// every further this many.
+const FAILED_READS_BEFORE_RESET: usize = 5;
+/// After this many failed reads in a row the touch screen counts as failing.
+const FAILED_READS_BEFORE_WARNING: usize = 3;
+
+/// I2C3 registers, `i2c::init` sets them up.
+const I2C3_START: usize = 0x4000_5c00;
+/// I2C3 control register 1, the first one.
+const I2C3_CR1: usize = I2C3_START;
+/// Peripheral enable.
+const CR1_PE: u32 = 1;
+
+/// GPIO port H mode register, I2C3 has SCL on PH7 and SDA on PH8.
+const GPIOH_MODER: usize = 0x4002_1c00;
+/// GPIO port H input data register.
+const GPIOH_IDR: usize = 0x4002_1c10;
+/// GPIO port H bit set/reset register, the upper half resets.
+const GPIOH_BSRR: usize = 0x4002_1c18;
+const SCL_PIN: u32 = 7;
+const SDA_PIN: u32 = 8;
+/// MODER value of a pin driven by the GPIO, the pins stay open drain.
+const MODE_OUTPUT: u32 = 0b01;
+/// MODER value of a pin driven by I2C3.
+const MODE_ALTERNATE: u32 = 0b10;
+/// Clock pulses that finish any byte a device may be sending, ACK included.
+const RECOVERY_PULSES: usize = 9;
+/// Reads of a GPIO register that take well over the 5 µs of half a 100 kHz
+/// clock period.
+const HALF_PERIOD_READS: usize = 400;
+
+pub struct TouchScreen {
+    i2c_3: i2c::I2C,
+    /// reads in a row that failed
+    failed_reads: usize,
+}
+
+impl TouchScreen {
+    pub fn new(i2c_3: i2c::I2C) -> TouchScreen {
+        TouchScreen {
+            i2c_3: i2c_3,
+            failed_reads: 0,
+        }
+    }
+
+    /**
+     * The points touched, mapped to screen coordinates by the board profile.
+     */
+    pub fn touches(&mut self) -> Result<Vec<(u16, u16)>, i2c::Error> {
+        let touches = self.retry(|i2c_3| touch::touches(i2c_3))?;
+        Ok(touches
+            .iter()
+            .map(|touch| profile::TOUCH.map((touch.x, touch.y)))
+            .collect())
+    }
+
+    /**
+     * Whether the touch controller answers with the right family id.
+     */
+    pub fn check(&mut self) -> Result<(), i2c::Error> {
+        self.retry(|i2c_3| touch::check_family_id(i2c_3))
+    }
+
+    /**
+     * Several reads in a row failed, the last one included.
+     */
+    pub fn is_failing(&self) -> bool {
+        self.failed_reads >= FAILED_READS_BEFORE_WARNING
+    }
+
+    /**
+     * Calls `read` until it succeeds, `ATTEMPTS` times at most, and resets
+     * the bus every `FAILED_READS_BEFORE_RESET` failures.
+     */
+    fn retry<T, F>(&mut self, mut read: F) -> Result<T, i2c::Error>
+    where
+        F: FnMut(&mut i2c::I2C) -> Result<T, i2c::Error>,
+    {
+        let mut result = read(&mut self.i2c_3);
+        for _ in 1..ATTEMPTS {
+            if result.is_ok() {
+                break;
+            }
+            result = read(&mut self.i2c_3);
+        }
+        match result {
+            Ok(_) => self.failed_reads = 0,
+            Err(_) => {
+                self.failed_reads += 1;
+                if self.failed_reads % FAILED_READS_BEFORE_RESET == 0 {
+                    self.recover_bus();
+                }
+            }
+        }
+        result
+    }
+}
+
+impl TouchScreen {
+    /**
+     * Frees a bus a device holds by clocking it and sending a STOP on the
+     * GPIO, then sets up I2C3 again.
+     */
+    fn recover_bus(&mut self) {
+        unsafe {
+            let cr1 = ptr::read_volatile(I2C3_CR1 as *const u32);
+            ptr::write_volatile(I2C3_CR1 as *mut u32, cr1 & !CR1_PE);
+
+            set_line(SCL_PIN, true);
+            set_line(SDA_PIN, true);
+            set_pin_mode(SCL_PIN, MODE_OUTPUT);
+            set_pin_mode(SDA_PIN, MODE_OUTPUT);
+            for _ in 0..RECOVERY_PULSES {
+                set_line(SCL_PIN, false);
+                set_line(SCL_PIN, true);
+            }
+            // STOP: SDA rises while SCL is high
+            set_line(SCL_PIN, false);
+            set_line(SDA_PIN, false);
+            set_line(SCL_PIN, true);
+            set_line(SDA_PIN, true);
+            if ptr::read_volatile(GPIOH_IDR as *const u32) & 1 << SDA_PIN == 0 {
+                hprintln!("I2C3 recovery: SDA is still held low");
+            }
+            set_pin_mode(SCL_PIN, MODE_ALTERNATE);
+            set_pin_mode(SDA_PIN, MODE_ALTERNATE);
+
+            // the board crate only hands out I2C3 once, the old handle to it
+            // is dropped right away
+            let registers = &mut *(I2C3_START as *mut board::i2c::I2c);
+            self.i2c_3 = i2c::init(registers);
+        }
+    }
+}
+
+/**
+ * Switches a pin of port H to `mode`.
+ */
+unsafe fn set_pin_mode(pin: u32, mode: u32) {
+    let moder = ptr::read_volatile(GPIOH_MODER as *const u32);
+    let moder = moder & !(0b11 << (2 * pin)) | mode << (2 * pin);
+    ptr::write_volatile(GPIOH_MODER as *mut u32, moder);
+}
+
+/**
+ * Releases (`high`) or pulls down an open drain pin of port H, then waits
+ * half a clock period.
+ */
+unsafe fn set_line(pin: u32, high: bool) {
+    let bit = if high { 1 << pin } else { 1 << (pin + 16) };
+    ptr::write_volatile(GPIOH_BSRR as *mut u32, bit);
+    for _ in 0..HALF_PERIOD_READS {
+        ptr::read_volatile(GPIOH_IDR as *const u32);
+    }
+}
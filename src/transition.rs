@@ -0,0 +1,85 @@
+//! Transitions between two screens.
+//!
+//! A transition first hides the old screen (`Phase::Out`), then the caller
+//! switches to the new screen and the transition shows it (`Phase::In`). It
+//! only keeps track of the timing; `Graphics::apply_transition` does the
+//! drawing with the LTDC layer registers, so the CPU stays free for the game.
+
+/// Coverage of a fully hidden screen, see `Transition::coverage`.
+pub const COVERED: u32 = 256;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Effect {
+    /// the layers fade out to a black background and the new screen fades in
+    Fade,
+    /// the background wipes over the screen from the left, then uncovers the new one
+    Wipe,
+    /// the screen slides out to the right, then the new one slides back in
+    Slide,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Phase {
+    Out,
+    In,
+    Done,
+}
+
+pub struct Transition {
+    pub effect: Effect,
+    /// only layer 2 (menus) takes part, layer 1 stays visible
+    pub overlay_only: bool,
+    phase: Phase,
+    phase_started_at: usize,
+    /// duration of each phase in ms
+    duration: usize,
+}
+
+impl Transition {
+    pub fn new(effect: Effect, overlay_only: bool, duration: usize, now: usize) -> Transition {
+        Transition {
+            effect: effect,
+            overlay_only: overlay_only,
+            phase: Phase::Out,
+            phase_started_at: now,
+            duration: duration.max(1),
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /**
+     * How much of the screen is hidden at `now`, from 0 (fully visible) to
+     * `COVERED`.
+     */
+    pub fn coverage(&self, now: usize) -> u32 {
+        let elapsed = now.wrapping_sub(self.phase_started_at).min(self.duration);
+        let progress = (elapsed * COVERED as usize / self.duration) as u32;
+        match self.phase {
+            Phase::Out => progress,
+            Phase::In => COVERED - progress,
+            Phase::Done => 0,
+        }
+    }
+
+    /**
+     * Moves on to the next phase once the current one is over. Returns
+     * `true` if the phase changed; after `Phase::Out` the new screen has to
+     * be set up.
+     */
+    pub fn advance(&mut self, now: usize) -> bool {
+        if self.phase == Phase::Done
+            || now.wrapping_sub(self.phase_started_at) < self.duration
+        {
+            return false;
+        }
+        self.phase = match self.phase {
+            Phase::Out => Phase::In,
+            _ => Phase::Done,
+        };
+        self.phase_started_at = now;
+        true
+    }
+}
@@ -0,0 +1,523 @@
+//! Touch widgets for the menus.
+//!
+//! Every widget owns its bounds, draws itself and keeps track of whether it
+//! is pressed, so a menu is declared once in `render` and hit-testing follows
+//! from the layout. Like `render`, nothing in here touches the hardware.
+
+use alloc::String;
+use alloc::Vec;
+use canvas::{Blend, Canvas, Color};
+use font;
+use sprite::{RotDirection, Sprite};
+
+const TEXT_SCALE: usize = 2;
+const CORNER_RADIUS: usize = 6;
+/// Extra pixels around a slider knob and toggle box that still count as a hit.
+const TOUCH_MARGIN: usize = 8;
+
+/**
+ * Colors of the widgets, see `Theme::ui`.
+ */
+#[derive(Clone, Copy)]
+pub struct Style {
+    pub foreground: Color,
+    pub background: Color,
+    pub pressed: Color,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn contains(&self, (x, y): (usize, usize)) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /**
+     * The rectangle grown by `margin` on every side.
+     */
+    pub fn grow(&self, margin: usize) -> Rect {
+        let x = self.x.saturating_sub(margin);
+        let y = self.y.saturating_sub(margin);
+        Rect::new(
+            x,
+            y,
+            self.x + self.width + margin - x,
+            self.y + self.height + margin - y,
+        )
+    }
+}
+
+/**
+ * What happened to a widget after a touch update.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Event {
+    /// a button was released while the finger was still on it
+    Clicked,
+    /// a toggle was switched, with the new state
+    Toggled(bool),
+    /// a slider was moved, with the new value
+    Changed(i32),
+}
+
+/**
+ * Text or an image that does not react to touches.
+ */
+pub struct Label {
+    pub bounds: Rect,
+    content: LabelContent,
+    color: Color,
+}
+
+enum LabelContent {
+    Text(String),
+    Image(&'static Sprite<u16>),
+}
+
+impl Label {
+    pub fn text(x: usize, y: usize, text: &str, color: Color) -> Label {
+        Label {
+            bounds: Rect::new(
+                x,
+                y,
+                font::text_width(text, TEXT_SCALE),
+                font::text_height(TEXT_SCALE),
+            ),
+            content: LabelContent::Text(String::from(text)),
+            color,
+        }
+    }
+
+    pub fn image(x: usize, y: usize, sprite: &'static Sprite<u16>) -> Label {
+        Label {
+            bounds: Rect::new(x, y, sprite.width, sprite.height),
+            content: LabelContent::Image(sprite),
+            color: Color::rgb(255, 255, 255),
+        }
+    }
+
+    /**
+     * Replaces the text, the bounds grow or shrink with it.
+     */
+    pub fn set_text(&mut self, text: &str) {
+        self.bounds.width = font::text_width(text, TEXT_SCALE);
+        self.content = LabelContent::Text(String::from(text));
+    }
+
+    fn draw<C: Canvas>(&self, canvas: &mut C) {
+        match self.content {
+            LabelContent::Text(ref text) => font::draw_text(
+                canvas,
+                self.bounds.x,
+                self.bounds.y,
+                text,
+                self.color,
+                TEXT_SCALE,
+            ),
+            LabelContent::Image(sprite) => canvas.blit(
+                sprite,
+                self.bounds.x,
+                self.bounds.y,
+                RotDirection::R0,
+                Blend::SkipTransparent,
+            ),
+        }
+    }
+}
+
+/**
+ * A rounded button with centered text.
+ */
+pub struct Button {
+    pub bounds: Rect,
+    text: String,
+    style: Style,
+    pressed: bool,
+}
+
+impl Button {
+    pub fn new(bounds: Rect, text: &str, style: Style) -> Button {
+        Button {
+            bounds,
+            text: String::from(text),
+            style,
+            pressed: false,
+        }
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text = String::from(text);
+    }
+
+    fn draw<C: Canvas>(&self, canvas: &mut C) {
+        let b = self.bounds;
+        let fill = if self.pressed {
+            self.style.pressed
+        } else {
+            self.style.background
+        };
+        canvas.fill_rounded_rect(b.x, b.y, b.width, b.height, CORNER_RADIUS, fill);
+        canvas.draw_rounded_rect(
+            b.x,
+            b.y,
+            b.width,
+            b.height,
+            CORNER_RADIUS,
+            self.style.foreground,
+        );
+        let text_width = font::text_width(&self.text, TEXT_SCALE);
+        let text_height = font::text_height(TEXT_SCALE);
+        font::draw_text(
+            canvas,
+            b.x + b.width.saturating_sub(text_width) / 2,
+            b.y + b.height.saturating_sub(text_height) / 2,
+            &self.text,
+            self.style.foreground,
+            TEXT_SCALE,
+        );
+    }
+}
+
+/**
+ * A button drawn from an image, pressing it draws a frame around the image.
+ */
+pub struct ImageButton {
+    pub bounds: Rect,
+    sprite: &'static Sprite<u16>,
+    style: Style,
+    pressed: bool,
+}
+
+impl ImageButton {
+    pub fn new(x: usize, y: usize, sprite: &'static Sprite<u16>, style: Style) -> ImageButton {
+        ImageButton {
+            bounds: Rect::new(x, y, sprite.width, sprite.height),
+            sprite,
+            style,
+            pressed: false,
+        }
+    }
+
+    fn draw<C: Canvas>(&self, canvas: &mut C) {
+        let b = self.bounds;
+        canvas.blit(
+            self.sprite,
+            b.x,
+            b.y,
+            RotDirection::R0,
+            Blend::SkipTransparent,
+        );
+        if self.pressed {
+            canvas.draw_rounded_rect(
+                b.x,
+                b.y,
+                b.width,
+                b.height,
+                CORNER_RADIUS,
+                self.style.pressed,
+            );
+        }
+    }
+}
+
+/**
+ * A check box with text to its right.
+ */
+pub struct Toggle {
+    pub bounds: Rect,
+    text: &'static str,
+    style: Style,
+    pub on: bool,
+    pressed: bool,
+}
+
+impl Toggle {
+    pub fn new(x: usize, y: usize, text: &'static str, on: bool, style: Style) -> Toggle {
+        let size = font::text_height(TEXT_SCALE) + 4;
+        Toggle {
+            bounds: Rect::new(x, y, size + 8 + font::text_width(text, TEXT_SCALE), size),
+            text,
+            style,
+            on,
+            pressed: false,
+        }
+    }
+
+    fn draw<C: Canvas>(&self, canvas: &mut C) {
+        let b = self.bounds;
+        let size = b.height;
+        let fill = if self.pressed {
+            self.style.pressed
+        } else {
+            self.style.background
+        };
+        canvas.fill_rect(b.x, b.y, size, size, fill);
+        canvas.draw_rect(b.x, b.y, size, size, self.style.foreground);
+        if self.on {
+            canvas.fill_rect(b.x + 4, b.y + 4, size - 8, size - 8, self.style.foreground);
+        }
+        font::draw_text(
+            canvas,
+            b.x + size + 8,
+            b.y + 2,
+            self.text,
+            self.style.foreground,
+            TEXT_SCALE,
+        );
+    }
+}
+
+/**
+ * A horizontal track with a knob, the value follows the finger.
+ */
+pub struct Slider {
+    pub bounds: Rect,
+    min: i32,
+    max: i32,
+    pub value: i32,
+    style: Style,
+    pressed: bool,
+}
+
+impl Slider {
+    pub fn new(bounds: Rect, min: i32, max: i32, value: i32, style: Style) -> Slider {
+        Slider {
+            bounds,
+            min,
+            max: max.max(min),
+            value: value.max(min).min(max),
+            style,
+            pressed: false,
+        }
+    }
+
+    /// x coordinate of the knob center
+    fn knob_x(&self) -> usize {
+        let b = self.bounds;
+        let range = (self.max - self.min).max(1) as usize;
+        b.x + (self.value - self.min) as usize * (b.width - 1) / range
+    }
+
+    fn value_at(&self, x: usize) -> i32 {
+        let b = self.bounds;
+        let offset = x.max(b.x).min(b.x + b.width - 1) - b.x;
+        let range = (self.max - self.min) as usize;
+        // round to the nearest value
+        self.min + ((offset * range + (b.width - 1) / 2) / (b.width - 1).max(1)) as i32
+    }
+
+    fn draw<C: Canvas>(&self, canvas: &mut C) {
+        let b = self.bounds;
+        let center = b.y + b.height / 2;
+        canvas.fill_rect(b.x, center - 1, b.width, 3, self.style.background);
+        let fill = if self.pressed {
+            self.style.pressed
+        } else {
+            self.style.foreground
+        };
+        let radius = (b.height / 2) as isize;
+        canvas.fill_circle((self.knob_x() as isize, center as isize), radius, fill);
+    }
+}
+
+pub enum Widget {
+    Label(Label),
+    Button(Button),
+    ImageButton(ImageButton),
+    Toggle(Toggle),
+    Slider(Slider),
+}
+
+impl Widget {
+    /**
+     * The area the widget draws on, the slider knob may reach past its track.
+     */
+    pub fn bounds(&self) -> Rect {
+        match *self {
+            Widget::Label(ref w) => w.bounds,
+            Widget::Button(ref w) => w.bounds,
+            Widget::ImageButton(ref w) => w.bounds,
+            Widget::Toggle(ref w) => w.bounds,
+            Widget::Slider(ref w) => {
+                let radius = w.bounds.height / 2;
+                Rect::new(
+                    w.bounds.x.saturating_sub(radius),
+                    w.bounds.y,
+                    w.bounds.width + 2 * radius,
+                    w.bounds.height + 1,
+                )
+            }
+        }
+    }
+
+    fn hit_area(&self) -> Rect {
+        match *self {
+            Widget::Toggle(_) | Widget::Slider(_) => self.bounds().grow(TOUCH_MARGIN),
+            _ => self.bounds(),
+        }
+    }
+
+    pub fn draw<C: Canvas>(&self, canvas: &mut C) {
+        match *self {
+            Widget::Label(ref w) => w.draw(canvas),
+            Widget::Button(ref w) => w.draw(canvas),
+            Widget::ImageButton(ref w) => w.draw(canvas),
+            Widget::Toggle(ref w) => w.draw(canvas),
+            Widget::Slider(ref w) => w.draw(canvas),
+        }
+    }
+
+    /**
+     * Erases the widget and draws it again.
+     */
+    pub fn redraw<C: Canvas>(&self, canvas: &mut C) {
+        let b = self.bounds();
+        canvas.fill_rect(b.x, b.y, b.width, b.height, Color::transparent());
+        self.draw(canvas);
+    }
+
+    fn pressed_mut(&mut self) -> Option<&mut bool> {
+        match *self {
+            Widget::Label(_) => None,
+            Widget::Button(ref mut w) => Some(&mut w.pressed),
+            Widget::ImageButton(ref mut w) => Some(&mut w.pressed),
+            Widget::Toggle(ref mut w) => Some(&mut w.pressed),
+            Widget::Slider(ref mut w) => Some(&mut w.pressed),
+        }
+    }
+
+    /**
+     * Updates the widget with the current touch, `None` if the screen is not
+     * touched. Returns the event it caused and whether it has to be redrawn.
+     *
+     * Buttons and toggles act when the finger is lifted on them, moving the
+     * finger away first cancels the press.
+     */
+    pub fn touch(&mut self, touch: Option<(usize, usize)>) -> (Option<Event>, bool) {
+        let hit = touch.map_or(false, |point| self.hit_area().contains(point));
+        // a slider keeps following a finger that slips off its track
+        let sticky = is_slider(self);
+        let was_pressed = match self.pressed_mut() {
+            Some(pressed) => {
+                let was_pressed = *pressed;
+                *pressed = hit || (touch.is_some() && was_pressed && sticky);
+                was_pressed
+            }
+            None => return (None, false),
+        };
+        let released = was_pressed && touch.is_none();
+        let event = match *self {
+            Widget::Button(_) | Widget::ImageButton(_) if released => Some(Event::Clicked),
+            Widget::Toggle(ref mut w) if released => {
+                w.on = !w.on;
+                Some(Event::Toggled(w.on))
+            }
+            Widget::Slider(ref mut w) if w.pressed => {
+                let value = w.value_at(touch.map_or(0, |(x, _)| x));
+                if value != w.value {
+                    w.value = value;
+                    Some(Event::Changed(value))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        let pressed = self.pressed_mut().map_or(false, |pressed| *pressed);
+        (event, event.is_some() || pressed != was_pressed)
+    }
+}
+
+fn is_slider(widget: &Widget) -> bool {
+    match *widget {
+        Widget::Slider(_) => true,
+        _ => false,
+    }
+}
+
+/**
+ * A set of widgets, each tagged with the action the caller associates with it.
+ */
+pub struct Menu<A: Copy> {
+    widgets: Vec<(A, Widget)>,
+}
+
+impl<A: Copy> Menu<A> {
+    pub fn new() -> Menu<A> {
+        Menu {
+            widgets: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, action: A, widget: Widget) {
+        self.widgets.push((action, widget));
+    }
+
+    pub fn widgets(&self) -> &[(A, Widget)] {
+        &self.widgets
+    }
+
+    pub fn widgets_mut(&mut self) -> &mut [(A, Widget)] {
+        &mut self.widgets
+    }
+
+    pub fn draw<C: Canvas>(&self, canvas: &mut C) {
+        for (_, widget) in &self.widgets {
+            widget.draw(canvas);
+        }
+    }
+
+    /**
+     * Passes the current touch to every widget, redraws the ones that changed
+     * and returns the first event with its action.
+     */
+    pub fn touch<C: Canvas>(
+        &mut self,
+        canvas: &mut C,
+        touch: Option<(usize, usize)>,
+    ) -> Option<(A, Event)> {
+        let mut result = None;
+        for &mut (action, ref mut widget) in &mut self.widgets {
+            let (event, changed) = widget.touch(touch);
+            if changed {
+                widget.redraw(canvas);
+            }
+            if result.is_none() {
+                result = event.map(|event| (action, event));
+            }
+        }
+        result
+    }
+}
+
+impl<A: Copy + PartialEq> Menu<A> {
+    /**
+     * Changes the text of the buttons tagged with `action` and redraws them.
+     */
+    pub fn set_button_text<C: Canvas>(&mut self, canvas: &mut C, action: A, text: &str) {
+        for &mut (widget_action, ref mut widget) in &mut self.widgets {
+            if widget_action != action {
+                continue;
+            }
+            if let Widget::Button(ref mut button) = *widget {
+                button.set_text(text);
+            }
+            widget.redraw(canvas);
+        }
+    }
+}